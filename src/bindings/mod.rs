@@ -0,0 +1,528 @@
+// This module defines the key bindings: the keys that trigger each in-game action, with presets,
+// conflict detection and a bindings file in the user config directory.
+//
+// Principles and design choices:
+//
+// - The main loop asks whether an Action is pressed or held instead of testing KeyCodes, so every
+//   in-game control can be remapped; each action accepts several keys.
+// - Menu navigation (arrows, Enter, Backspace), the practice keys (1-9, Backspace, G, Tab) and Delete
+//   (which clears an action on the controls screen) stay fixed, so the game can always be driven back
+//   to the controls screen; these keys cannot be bound, and problem() reports them if they are.
+// - A key bound to two actions is a conflict: the controls screen marks it and refuses to save until
+//   it is resolved, and a bindings file with a conflict is reported and replaced by the default keys,
+//   so two actions never fire on one key.
+// - The controls screen edits a copy of the bindings: S saves it once it has no problem, Backspace
+//   discards it, and Escape cancels a key capture (so quitting is off while the screen is open).
+// - Keys are written by name in the TOML file (e.g. move_left = ["Left", "A"]), which reads the same on
+//   any keyboard layout; unknown names are reported and skipped, and actions missing from the file
+//   keep their default keys.
+// - Quit is an action like the others, bound to Escape by default (no longer Q), and the main loop
+//   asks for a second press before quitting.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+/// File name of the bindings inside the config directory.
+const BINDINGS_FILE: &str = "bindings.toml";
+
+/// Most keys bound to one action.
+pub const MAX_KEYS: usize = 3;
+
+/// Enum representing the remappable in-game actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    Rotate,
+    Hold,
+    Pause,
+    Retry,
+    Rewind,
+    Quit,
+}
+
+/// Implementation of Action methods.
+impl Action {
+    /// All actions, in the order of the controls screen.
+    pub const ALL: [Action; 10] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::Rotate,
+        Action::Hold,
+        Action::Pause,
+        Action::Retry,
+        Action::Rewind,
+        Action::Quit,
+    ];
+
+    /// Returns the display name of the action.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::Rotate => "Rotate",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+            Action::Retry => "Retry",
+            Action::Rewind => "Rewind",
+            Action::Quit => "Quit",
+        }
+    }
+
+    /// Returns the name of the action in the bindings file.
+    fn key(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::Rotate => "rotate",
+            Action::Hold => "hold",
+            Action::Pause => "pause",
+            Action::Retry => "retry",
+            Action::Rewind => "rewind",
+            Action::Quit => "quit",
+        }
+    }
+
+    /// Returns the position of the action in ALL.
+    fn index(&self) -> usize {
+        Self::ALL.iter().position(|a| a == self).unwrap_or(0)
+    }
+}
+
+/// Enum representing the binding presets.
+///  - Guideline: The modern default: arrows, Up/X to rotate, C/Shift to hold, Space to hard drop.
+///  - Arrows: Everything on and around the arrow keys, for one hand.
+///  - Wasd: A/D to move, S to soft drop, W to rotate, with the thumb on Space and Shift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Guideline,
+    Arrows,
+    Wasd,
+}
+
+/// Implementation of Preset methods.
+impl Preset {
+    /// All presets, in the order they are cycled through.
+    pub const ALL: [Preset; 3] = [Preset::Guideline, Preset::Arrows, Preset::Wasd];
+
+    /// Returns the display name of the preset.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Guideline => "Guideline",
+            Preset::Arrows => "Arrows",
+            Preset::Wasd => "WASD",
+        }
+    }
+
+    /// Returns the keys of an action in this preset.
+    fn keys(&self, action: Action) -> Vec<KeyCode> {
+        use KeyCode::*;
+        match (self, action) {
+            (_, Action::Quit) => vec![Escape],
+            (_, Action::Retry) => vec![R],
+            (Preset::Guideline, Action::MoveLeft) => vec![Left],
+            (Preset::Guideline, Action::MoveRight) => vec![Right],
+            (Preset::Guideline, Action::SoftDrop) => vec![Down],
+            (Preset::Guideline, Action::HardDrop) => vec![Space],
+            (Preset::Guideline, Action::Rotate) => vec![Up, X],
+            (Preset::Guideline, Action::Hold) => vec![C, LeftShift],
+            (Preset::Guideline, Action::Pause) => vec![Enter, P],
+            (Preset::Guideline, Action::Rewind) => vec![Z],
+            (Preset::Arrows, Action::MoveLeft) => vec![Left],
+            (Preset::Arrows, Action::MoveRight) => vec![Right],
+            (Preset::Arrows, Action::SoftDrop) => vec![Down],
+            (Preset::Arrows, Action::HardDrop) => vec![RightShift, Space],
+            (Preset::Arrows, Action::Rotate) => vec![Up],
+            (Preset::Arrows, Action::Hold) => vec![RightControl],
+            (Preset::Arrows, Action::Pause) => vec![Enter, P],
+            (Preset::Arrows, Action::Rewind) => vec![End],
+            (Preset::Wasd, Action::MoveLeft) => vec![A],
+            (Preset::Wasd, Action::MoveRight) => vec![D],
+            (Preset::Wasd, Action::SoftDrop) => vec![S],
+            (Preset::Wasd, Action::HardDrop) => vec![Space],
+            (Preset::Wasd, Action::Rotate) => vec![W],
+            (Preset::Wasd, Action::Hold) => vec![LeftShift],
+            (Preset::Wasd, Action::Pause) => vec![Enter, P],
+            (Preset::Wasd, Action::Rewind) => vec![Q],
+        }
+    }
+}
+
+/// Keys with a fixed meaning during play or on the controls screen, which cannot be bound to an action.
+const RESERVED_KEYS: [KeyCode; 13] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Backspace,
+    KeyCode::G,
+    KeyCode::Tab,
+    KeyCode::Delete,
+];
+
+/// Keys that can be bound, with their names in the bindings file and on screen.
+const KEY_NAMES: [(KeyCode, &str); 75] = [
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Escape, "Escape"),
+    (KeyCode::LeftShift, "LShift"),
+    (KeyCode::RightShift, "RShift"),
+    (KeyCode::LeftControl, "LCtrl"),
+    (KeyCode::RightControl, "RCtrl"),
+    (KeyCode::LeftAlt, "LAlt"),
+    (KeyCode::RightAlt, "RAlt"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Delete, "Delete"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Comma, "Comma"),
+    (KeyCode::Period, "Period"),
+    (KeyCode::Slash, "Slash"),
+    (KeyCode::Semicolon, "Semicolon"),
+    (KeyCode::Apostrophe, "Apostrophe"),
+    (KeyCode::Minus, "Minus"),
+    (KeyCode::Equal, "Equal"),
+    (KeyCode::LeftBracket, "LBracket"),
+    (KeyCode::RightBracket, "RBracket"),
+    (KeyCode::Backslash, "Backslash"),
+    (KeyCode::GraveAccent, "Grave"),
+    (KeyCode::Kp0, "Num0"),
+    (KeyCode::Kp1, "Num1"),
+    (KeyCode::Kp2, "Num2"),
+    (KeyCode::Kp3, "Num3"),
+    (KeyCode::Kp4, "Num4"),
+    (KeyCode::Kp5, "Num5"),
+    (KeyCode::Kp6, "Num6"),
+    (KeyCode::Kp7, "Num7"),
+    (KeyCode::Kp8, "Num8"),
+    (KeyCode::Kp9, "Num9"),
+    (KeyCode::KpEnter, "NumEnter"),
+    (KeyCode::KpAdd, "NumAdd"),
+    (KeyCode::KpSubtract, "NumSubtract"),
+    (KeyCode::F1, "F1"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F4, "F4"),
+    (KeyCode::F5, "F5"),
+    (KeyCode::F6, "F6"),
+];
+
+/// Returns the name of a key, or `None` if the key cannot be bound.
+pub fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(k, _)| *k == key).map(|(_, name)| *name)
+}
+
+/// Returns the key with the given name, if it can be bound.
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|(key, _)| *key)
+}
+
+/// Struct holding the keys bound to every action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    /// Keys of each action, in the order of Action::ALL.
+    keys: [Vec<KeyCode>; Action::ALL.len()],
+}
+
+/// Raw bindings file as written on disk: action name to key names.
+#[derive(Default, Serialize, Deserialize)]
+struct BindingsFile {
+    #[serde(default)]
+    keys: BTreeMap<String, Vec<String>>,
+}
+
+/// Implementation of Bindings methods.
+impl Bindings {
+    /// Creates the bindings of a preset.
+    pub fn preset(preset: Preset) -> Self {
+        Self {
+            keys: Action::ALL.map(|action| preset.keys(action)),
+        }
+    }
+
+    /// Loads the bindings from the config directory.
+    ///
+    /// # Returns
+    /// * The bindings, and a message for every problem found; unknown entries and extra keys are
+    ///   skipped and actions without a valid key keep their default keys. Bindings that still share
+    ///   a key between actions are replaced by the Guideline preset.
+    pub fn load() -> (Self, Vec<String>) {
+        match fs::read_to_string(storage::config_dir().join(BINDINGS_FILE)) {
+            Ok(text) => Self::parse(&text),
+            Err(_) => (Self::default(), Vec::new()),
+        }
+    }
+
+    /// Parses the bindings from the text of a bindings file, as described for load().
+    fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut bindings = Self::preset(Preset::Guideline);
+        let file: BindingsFile = match toml::from_str(text) {
+            Ok(file) => file,
+            Err(e) => return (bindings, vec![e.message().to_owned()]),
+        };
+        let mut errors = Vec::new();
+        for (name, key_names) in &file.keys {
+            let Some(action) = Action::ALL.iter().find(|a| a.key() == name) else {
+                errors.push(format!("unknown action {:?}", name));
+                continue;
+            };
+            let mut keys = Vec::new();
+            for key_name in key_names {
+                match key_from_name(key_name).filter(|key| !RESERVED_KEYS.contains(key)) {
+                    Some(key) if keys.contains(&key) => {
+                        errors.push(format!("{} lists {} twice", name, key_name));
+                    }
+                    Some(_) if keys.len() == MAX_KEYS => {
+                        errors.push(format!("{} has more than {} keys, {} is ignored", name, MAX_KEYS, key_name));
+                    }
+                    Some(key) => keys.push(key),
+                    None => errors.push(format!("{} cannot use key {:?}", name, key_name)),
+                }
+            }
+            if !keys.is_empty() {
+                bindings.keys[action.index()] = keys;
+            }
+        }
+        if let Some(problem) = bindings.problem() {
+            errors.insert(0, format!("{}, using the default keys", problem));
+            bindings = Self::preset(Preset::Guideline);
+        }
+        (bindings, errors)
+    }
+
+    /// Saves the bindings to the config directory.
+    pub fn save(&self) -> Result<(), String> {
+        let keys = Action::ALL
+            .iter()
+            .map(|action| {
+                let names = self.keys(*action).iter().filter_map(|key| key_name(*key));
+                (action.key().to_owned(), names.map(str::to_owned).collect())
+            })
+            .collect();
+        storage::save_in(&storage::config_dir(), BINDINGS_FILE, &BindingsFile { keys })
+    }
+
+    /// Returns the keys bound to an action.
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        &self.keys[action.index()]
+    }
+
+    /// Whether a key of the action was pressed this frame.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.keys(action).iter().any(|key| is_key_pressed(*key))
+    }
+
+    /// Whether a key of the action is held down.
+    pub fn down(&self, action: Action) -> bool {
+        self.keys(action).iter().any(|key| is_key_down(*key))
+    }
+
+    /// Returns the keys of an action as text, e.g. "Up, X".
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<&str> = self.keys(action).iter().filter_map(|key| key_name(*key)).collect();
+        if names.is_empty() { "-".to_owned() } else { names.join(", ") }
+    }
+
+    /// Adds a key to an action, replacing its oldest key when it already has MAX_KEYS.
+    ///
+    /// # Arguments
+    /// * `action` - The action to bind
+    /// * `key` - The key pressed
+    /// # Returns
+    /// * An error message if the key cannot be bound.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Result<(), String> {
+        let Some(name) = key_name(key).filter(|_| !RESERVED_KEYS.contains(&key)) else {
+            return Err("This key cannot be bound".to_owned());
+        };
+        let keys = &mut self.keys[action.index()];
+        if keys.contains(&key) {
+            return Err(format!("{} is already bound to {}", name, action.name()));
+        }
+        if keys.len() == MAX_KEYS {
+            keys.remove(0);
+        }
+        keys.push(key);
+        Ok(())
+    }
+
+    /// Removes every key of an action.
+    pub fn clear(&mut self, action: Action) {
+        self.keys[action.index()].clear();
+    }
+
+    /// Whether the action shares a key with another action.
+    pub fn has_conflict(&self, action: Action) -> bool {
+        self.keys(action).iter().any(|key| {
+            Action::ALL
+                .iter()
+                .any(|other| *other != action && self.keys(*other).contains(key))
+        })
+    }
+
+    /// Returns the first problem that prevents saving: an action without keys, a reserved key or a
+    /// conflict.
+    pub fn problem(&self) -> Option<String> {
+        Action::ALL.iter().find_map(|action| {
+            let reserved = self.keys(*action).iter().find(|key| RESERVED_KEYS.contains(key));
+            if self.keys(*action).is_empty() {
+                Some(format!("{} has no key", action.name()))
+            } else if let Some(key) = reserved {
+                Some(format!("{} uses the reserved key {}", action.name(), key_name(*key).unwrap_or("?")))
+            } else if self.has_conflict(*action) {
+                Some(format!("{} shares a key with another action", action.name()))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Implementation of Default for Bindings.
+impl Default for Bindings {
+    fn default() -> Self {
+        Self::preset(Preset::Guideline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_have_no_problem() {
+        for preset in Preset::ALL {
+            assert_eq!(Bindings::preset(preset).problem(), None, "{}", preset.name());
+        }
+    }
+
+    #[test]
+    fn conflicts_are_detected() {
+        let mut bindings = Bindings::default();
+        bindings.bind(Action::Hold, KeyCode::Space).unwrap();
+        assert!(bindings.has_conflict(Action::Hold));
+        assert!(bindings.has_conflict(Action::HardDrop));
+        assert!(!bindings.has_conflict(Action::Rotate));
+        assert!(bindings.problem().unwrap().contains("shares a key"));
+
+        // Clearing one side leaves an action without keys, which is a problem too
+        bindings.clear(Action::Hold);
+        assert!(!bindings.has_conflict(Action::HardDrop));
+        assert_eq!(bindings.problem().unwrap(), "Hold has no key");
+        bindings.bind(Action::Hold, KeyCode::V).unwrap();
+        assert_eq!(bindings.problem(), None);
+    }
+
+    #[test]
+    fn reserved_keys_are_refused() {
+        let mut bindings = Bindings::default();
+        for key in RESERVED_KEYS {
+            assert!(bindings.bind(Action::Rewind, key).is_err(), "{:?}", key);
+        }
+        // So are keys without a name
+        assert!(bindings.bind(Action::Rewind, KeyCode::F12).is_err());
+        // A reserved key that got in anyway is reported
+        bindings.keys[Action::Rewind.index()] = vec![KeyCode::Delete];
+        assert!(bindings.problem().unwrap().contains("reserved key Delete"));
+    }
+
+    #[test]
+    fn binding_keeps_at_most_max_keys() {
+        let mut bindings = Bindings::default();
+        bindings.clear(Action::Rewind);
+        for key in [KeyCode::V, KeyCode::B, KeyCode::N, KeyCode::M] {
+            bindings.bind(Action::Rewind, key).unwrap();
+        }
+        assert_eq!(bindings.keys(Action::Rewind), [KeyCode::B, KeyCode::N, KeyCode::M]);
+        assert!(bindings.bind(Action::Rewind, KeyCode::M).is_err());
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let bindings = Bindings::preset(Preset::Wasd);
+        let keys = Action::ALL
+            .iter()
+            .map(|action| {
+                let names = bindings.keys(*action).iter().map(|key| key_name(*key).unwrap().to_owned());
+                (action.key().to_owned(), names.collect())
+            })
+            .collect();
+        let text = toml::to_string(&BindingsFile { keys }).unwrap();
+        assert_eq!(Bindings::parse(&text), (bindings, Vec::new()));
+    }
+
+    #[test]
+    fn file_problems_are_reported() {
+        let (bindings, errors) = Bindings::parse(
+            "[keys]\nhold = [\"V\", \"V\", \"Tab\", \"Nope\"]\nrotate = [\"Up\", \"X\", \"W\", \"E\"]\njump = [\"J\"]",
+        );
+        assert_eq!(bindings.keys(Action::Hold), [KeyCode::V]);
+        assert_eq!(bindings.keys(Action::Rotate), [KeyCode::Up, KeyCode::X, KeyCode::W]);
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(bindings.problem().is_none());
+    }
+
+    #[test]
+    fn conflicting_file_falls_back_to_the_preset() {
+        let (bindings, errors) = Bindings::parse("[keys]\nhold = [\"Space\"]");
+        assert_eq!(bindings, Bindings::preset(Preset::Guideline));
+        assert!(errors[0].contains("shares a key") && errors[0].contains("default keys"), "{:?}", errors);
+
+        // A broken file keeps the defaults too
+        let (bindings, errors) = Bindings::parse("[keys");
+        assert_eq!(bindings, Bindings::default());
+        assert_eq!(errors.len(), 1);
+    }
+}
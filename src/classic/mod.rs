@@ -0,0 +1,58 @@
+// This module defines the rules of Classic mode, modelled on the NES game: its gravity table,
+// line clear scoring and level progression from a selectable starting level.
+//
+// Principles and design choices:
+//
+// - Gravity is written as the number of frames per row at each level, like the original table, and
+//   converted to seconds with the NES frame rate; the Difficulty fall delays are not used.
+// - Scoring is 40/100/300/1200 points for 1-4 lines, multiplied by the level plus one.
+// - Starting on a higher level delays the first level up, as on the NES: the first level up happens
+//   after the lines of every level below the start (capped), then every 10 lines.
+// - The other Classic rules (no hold, no wall kicks, single next piece, NES randomizer) are queried
+//   through GameMode and the PieceQueue, like the rules of the other modes.
+
+/// Frames per second of the NES.
+const FRAME_RATE: f32 = 60.0988;
+
+/// Highest level selectable at the start.
+pub const MAX_START_LEVEL: u32 = 19;
+
+/// Frames per row for levels 0 to 28; level 29 and above drop one row per frame.
+const GRAVITY_FRAMES: [u32; 29] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+/// Points for clearing 1, 2, 3 and 4 lines at level 0.
+const LINE_SCORES: [u32; 4] = [40, 100, 300, 1200];
+
+/// Returns the time (in seconds) the piece takes to fall one row at the given level.
+///
+/// # Arguments
+/// * `level` - The current level
+pub fn fall_delay(level: usize) -> f32 {
+    GRAVITY_FRAMES.get(level).copied().unwrap_or(1) as f32 / FRAME_RATE
+}
+
+/// Returns the points for a line clear.
+///
+/// # Arguments
+/// * `lines` - The number of lines cleared at once (1 to 4)
+/// * `level` - The current level
+pub fn line_score(lines: u32, level: usize) -> u32 {
+    let index = (lines.clamp(1, 4) - 1) as usize;
+    LINE_SCORES[index] * (level as u32 + 1)
+}
+
+/// Returns the level reached after clearing the given number of lines.
+///
+/// # Arguments
+/// * `start_level` - The level selected at the start
+/// * `lines` - The total number of lines cleared
+pub fn level_for_lines(start_level: u32, lines: u32) -> usize {
+    let first_level_up = (start_level * 10 + 10).min((start_level * 10).saturating_sub(50).max(100));
+    if lines < first_level_up {
+        start_level as usize
+    } else {
+        (start_level + 1 + (lines - first_level_up) / 10) as usize
+    }
+}
@@ -0,0 +1,69 @@
+// This module defines the board of the 4-wide combo trainer: a 4-column well in the middle of the
+// grid, flanked by filled columns, with a few residue blocks at the bottom of the well.
+//
+// Principles and design choices:
+//
+// - The well is carved out of the normal 10-column grid, so the engine, collision and drawing code
+//   work unchanged; the side columns are walls that are refilled after every lock, so they never run out.
+// - The 4-wide well and its residues are made for tetrominoes, so the combo mode always deals the
+//   standard set (see GameMode::has_fixed_pieces); wider pieces would not fit the well.
+// - Every game starts from a residue picked at random from the common 3-block setups (and randomly
+//   mirrored), so each game drills a different start.
+// - Combo counting itself lives in GameInfo, which counts every line clearing piece in every mode;
+//   this module only builds the board.
+
+use macroquad::prelude::*;
+use macroquad::rand::gen_range;
+
+use crate::game::{GARBAGE_COLOR, Grid};
+use crate::parameters::{GRID_HEIGHT, GRID_WIDTH};
+
+/// First column of the well.
+const WELL_LEFT: usize = 3;
+
+/// Number of columns in the well.
+const WELL_WIDTH: usize = 4;
+
+/// Color of the wall columns, darker than garbage so the well stands out.
+const WALL_COLOR: Color = DARKGRAY;
+
+/// Residue setups of 3 blocks, as the two bottom rows of the well (top row first, 'X' is a block).
+const RESIDUES: [[&str; 2]; 4] = [
+    ["....", "XXX."],
+    ["X...", "XX.."],
+    ["....", "XX.X"],
+    [".X..", "XX.."],
+];
+
+/// Fills every cell outside the well, leaving the well itself untouched.
+///
+/// # Arguments
+/// * `grid` - The grid to fill
+pub fn fill_walls(grid: &mut Grid) {
+    for row in grid.iter_mut() {
+        for (col, cell) in row.iter_mut().enumerate() {
+            if !(WELL_LEFT..WELL_LEFT + WELL_WIDTH).contains(&col) {
+                *cell = Some(WALL_COLOR);
+            }
+        }
+    }
+}
+
+/// Builds the starting board: walls on both sides and a random residue at the bottom of the well.
+pub fn start_grid() -> Grid {
+    let mut grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+    fill_walls(&mut grid);
+
+    let residue = RESIDUES[gen_range(0, RESIDUES.len())];
+    let mirrored = gen_range(0, 2) == 1;
+    for (i, pattern) in residue.iter().enumerate() {
+        let row = GRID_HEIGHT - residue.len() + i;
+        for (j, cell) in pattern.chars().enumerate() {
+            let col = if mirrored { WELL_WIDTH - 1 - j } else { j };
+            if cell == 'X' {
+                grid[row][WELL_LEFT + col] = Some(GARBAGE_COLOR);
+            }
+        }
+    }
+    grid
+}
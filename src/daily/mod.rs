@@ -0,0 +1,120 @@
+// This module defines the daily challenge: a score attack whose piece sequence is seeded from the
+// date, so every player gets the same game on the same day, plus the local history of daily results.
+//
+// Principles and design choices:
+//
+// - The date is taken in UTC and turned into a seed with a stable hash, so players in different
+//   time zones share the same daily game and the seed never depends on the platform.
+// - The game is dealt from the standard pieces at a fixed difficulty, so daily results are comparable.
+// - Only the first game of a day is scored. The attempt is written to the history as soon as it starts,
+//   so leaving it midway still uses it up; later games that day are unranked practice.
+// - The daily pieces come from a bag with its own seeded generator, so the rest of the session keeps
+//   its usual random games.
+// - The history is a plain serde struct persisted through the storage module (atomic TOML writes).
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+/// Length of the daily score attack, in seconds.
+pub const DAILY_SECONDS: u32 = 120;
+
+/// File name of the daily history inside the data directory.
+const HISTORY_FILE: &str = "daily.toml";
+
+/// Returns today's date in UTC as "YYYY-MM-DD".
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Returns the random seed of the daily game for the given date (FNV-1a hash of the date text).
+///
+/// # Arguments
+/// * `date` - The date as "YYYY-MM-DD"
+pub fn seed(date: &str) -> u64 {
+    date.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Converts a number of days since 1970-01-01 into a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Scored result of one daily challenge.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyResult {
+    pub date: String,
+    pub score: u32,
+    pub lines: u32,
+    /// Whether the game was played to the end; an abandoned attempt keeps a score of 0.
+    #[serde(default = "finished_default")]
+    pub finished: bool,
+}
+
+/// Results saved before abandoned attempts were recorded were all finished games.
+fn finished_default() -> bool {
+    true
+}
+
+/// Scored daily results, oldest first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DailyHistory {
+    #[serde(default)]
+    pub results: Vec<DailyResult>,
+}
+
+/// Implementation of DailyHistory methods.
+impl DailyHistory {
+    /// Loads the daily history from the data directory.
+    pub fn load() -> Self {
+        storage::load(HISTORY_FILE)
+    }
+
+    /// Returns the scored result of the given day, if it was played.
+    pub fn result(&self, date: &str) -> Option<&DailyResult> {
+        self.results.iter().find(|result| result.date == date)
+    }
+
+    /// Uses up the scored attempt of a day as its game starts, and saves the history.
+    ///
+    /// # Returns
+    /// * An error message if the history could not be saved.
+    pub fn start(&mut self, date: &str) -> Result<(), String> {
+        self.record(DailyResult {
+            date: date.to_owned(),
+            score: 0,
+            lines: 0,
+            finished: false,
+        })
+    }
+
+    /// Records the scored result of a day, replacing its started attempt, and saves the history.
+    ///
+    /// # Returns
+    /// * An error message if the history could not be saved.
+    pub fn record(&mut self, result: DailyResult) -> Result<(), String> {
+        match self.results.iter_mut().find(|r| r.date == result.date) {
+            Some(existing) => *existing = result,
+            None => self.results.push(result),
+        }
+        storage::save(HISTORY_FILE, self)
+    }
+}
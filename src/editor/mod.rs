@@ -0,0 +1,341 @@
+// This module defines the board editor, where a starting grid and piece queue are built with the mouse
+// and keyboard, saved to or loaded from files, and launched into practice.
+//
+// Principles and design choices:
+//
+// - The editor works on a plain Grid, so an edited board can be handed to the engine as it is.
+// - The brush is one of the standard piece colors or garbage; the left mouse button applies the current
+//   tool and the right button erases: Paint sets single cells (dragging paints a stroke), Fill Row fills
+//   the clicked row leaving the clicked cell as its hole, and Shift Row inserts an empty row at the clicked
+//   row (pushing the stack up) or, with the right button, removes it.
+// - The queue is typed with the standard piece letters; boards are saved in numbered slots in the data
+//   directory using the puzzle board format, so a saved board can be copied into a puzzle pack.
+// - Drawing reuses the playfield drawing, with the tools and hints in the side panel.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Engine;
+use crate::game::{GARBAGE_COLOR, Grid, draw_grid_blocks};
+use crate::grid::draw_grid;
+use crate::parameters::{Difficulty, GRID_HEIGHT, GRID_WIDTH, block_size};
+use crate::piece_set::PieceSet;
+use crate::puzzle::{board_rows, parse_board, parse_pieces};
+use crate::storage;
+use crate::tetromino::COLORS;
+
+/// Panel layout constants.
+const FONT_SIZE: f32 = 20.0;
+const HINT_FONT_SIZE: f32 = 16.0;
+const SWATCH_SIZE: f32 = 30.0;
+const SWATCH_COLUMNS: usize = 4;
+const PALETTE_Y: f32 = 46.0;
+
+/// Left edge of the panel, right of the grid (which depends on the block size).
+fn panel_x() -> f32 {
+    GRID_WIDTH as f32 * block_size() + 20.0
+}
+
+/// Number of save slots.
+const SLOTS: usize = 9;
+
+/// Piece letters accepted in the queue, in the order of the standard piece set.
+const PIECE_LETTERS: &str = "IOSZJLT";
+
+/// Editing tools applied with the left mouse button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    Paint,
+    FillRow,
+    ShiftRow,
+}
+
+/// Implementation of Tool methods.
+impl Tool {
+    const ALL: [Tool; 3] = [Tool::Paint, Tool::FillRow, Tool::ShiftRow];
+
+    /// Returns the display name of the tool.
+    fn name(&self) -> &'static str {
+        match self {
+            Tool::Paint => "Paint",
+            Tool::FillRow => "Fill row",
+            Tool::ShiftRow => "Shift row",
+        }
+    }
+}
+
+/// A board saved by the editor, in the puzzle board format.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BoardFile {
+    #[serde(default)]
+    board: Vec<String>,
+    #[serde(default)]
+    pieces: String,
+}
+
+/// Struct holding the board editor state.
+pub struct Editor {
+    pub grid: Grid,
+    /// The typed piece queue, as standard piece letters.
+    queue: String,
+    /// Selected brush: a standard piece color, or garbage after the last color.
+    brush: usize,
+    tool: Tool,
+    slot: usize,
+    message: Option<(String, Color)>,
+}
+
+/// Implementation of Editor methods.
+impl Editor {
+    /// Creates an editor with an empty board and queue.
+    pub fn new() -> Self {
+        Self {
+            grid: [[None; GRID_WIDTH]; GRID_HEIGHT],
+            queue: String::new(),
+            brush: COLORS.len(),
+            tool: Tool::Paint,
+            slot: 1,
+            message: None,
+        }
+    }
+
+    /// Prepares the editor to be shown, dropping characters typed on other screens.
+    pub fn open(&mut self) {
+        clear_input_queue();
+        self.message = None;
+    }
+
+    /// Handles mouse editing and editor keys for this frame.
+    ///
+    /// # Returns
+    /// * `true` if the player pressed Enter to play the board in practice, `false` otherwise.
+    pub fn update(&mut self) -> bool {
+        self.update_mouse();
+
+        // Typed queue (the input queue returns the most recent character first)
+        let mut typed = Vec::new();
+        while let Some(c) = get_char_pressed() {
+            typed.push(c.to_ascii_uppercase());
+        }
+        self.queue
+            .extend(typed.into_iter().rev().filter(|c| PIECE_LETTERS.contains(*c)));
+        if is_key_pressed(KeyCode::Backspace) {
+            self.queue.pop();
+        }
+
+        // Brush, tool and slot selection
+        if is_key_pressed(KeyCode::Left) {
+            self.brush = cycle(self.brush, -1, COLORS.len() + 1);
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.brush = cycle(self.brush, 1, COLORS.len() + 1);
+        }
+        if is_key_pressed(KeyCode::Up) || is_key_pressed(KeyCode::Down) {
+            let step = if is_key_pressed(KeyCode::Up) { -1 } else { 1 };
+            let idx = Tool::ALL.iter().position(|t| *t == self.tool).unwrap_or(0);
+            self.tool = Tool::ALL[cycle(idx, step, Tool::ALL.len())];
+        }
+        if is_key_pressed(KeyCode::PageUp) {
+            self.slot = cycle(self.slot - 1, 1, SLOTS) + 1;
+        }
+        if is_key_pressed(KeyCode::PageDown) {
+            self.slot = cycle(self.slot - 1, -1, SLOTS) + 1;
+        }
+
+        // Board and file commands
+        if is_key_pressed(KeyCode::Delete) {
+            self.grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+        }
+        if is_key_pressed(KeyCode::F2) {
+            self.save();
+        }
+        if is_key_pressed(KeyCode::F3) {
+            self.load();
+        }
+        is_key_pressed(KeyCode::Enter)
+    }
+
+    /// Applies the current tool at the mouse position.
+    fn update_mouse(&mut self) {
+        let (mouse_x, mouse_y) = mouse_position();
+        let x = (mouse_x / block_size()).floor();
+        let y = (mouse_y / block_size()).floor();
+        if x < 0.0 || y < 0.0 || x >= GRID_WIDTH as f32 || y >= GRID_HEIGHT as f32 {
+            // Clicking a palette swatch selects the brush
+            if is_mouse_button_pressed(MouseButton::Left)
+                && let Some(brush) = swatch_at(mouse_x, mouse_y)
+            {
+                self.brush = brush;
+            }
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+
+        match self.tool {
+            Tool::Paint => {
+                if is_mouse_button_down(MouseButton::Left) {
+                    self.grid[y][x] = Some(self.brush_color());
+                } else if is_mouse_button_down(MouseButton::Right) {
+                    self.grid[y][x] = None;
+                }
+            }
+            Tool::FillRow => {
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    let color = self.brush_color();
+                    for (col, cell) in self.grid[y].iter_mut().enumerate() {
+                        *cell = if col == x { None } else { Some(color) };
+                    }
+                } else if is_mouse_button_pressed(MouseButton::Right) {
+                    self.grid[y] = [None; GRID_WIDTH];
+                }
+            }
+            Tool::ShiftRow => {
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    // Push the clicked row and everything above it up, leaving an empty row
+                    for row in 0..y {
+                        self.grid[row] = self.grid[row + 1];
+                    }
+                    self.grid[y] = [None; GRID_WIDTH];
+                } else if is_mouse_button_pressed(MouseButton::Right) {
+                    // Remove the clicked row, letting everything above it down
+                    for row in (1..=y).rev() {
+                        self.grid[row] = self.grid[row - 1];
+                    }
+                    self.grid[0] = [None; GRID_WIDTH];
+                }
+            }
+        }
+    }
+
+    /// Returns the color painted by the current brush.
+    fn brush_color(&self) -> Color {
+        COLORS.get(self.brush).copied().unwrap_or(GARBAGE_COLOR)
+    }
+
+    /// Returns the name of the file of the current save slot.
+    fn slot_file(&self) -> String {
+        format!("board-{}.toml", self.slot)
+    }
+
+    /// Saves the board and queue to the current slot.
+    fn save(&mut self) {
+        let file = BoardFile {
+            board: board_rows(&self.grid),
+            pieces: self.queue.clone(),
+        };
+        self.message = Some(match storage::save(&self.slot_file(), &file) {
+            Ok(()) => (format!("Saved slot {}", self.slot), GREEN),
+            Err(e) => (format!("Save failed: {}", e), RED),
+        });
+    }
+
+    /// Loads the board and queue from the current slot.
+    fn load(&mut self) {
+        if !storage::data_dir().join(self.slot_file()).exists() {
+            self.message = Some((format!("Slot {} is empty", self.slot), GRAY));
+            return;
+        }
+        let file: BoardFile = storage::load(&self.slot_file());
+        let loaded = parse_board(&file.board, &PieceSet::standard())
+            .and_then(|grid| parse_pieces(&file.pieces).map(|_| grid));
+        self.message = Some(match loaded {
+            Ok(grid) => {
+                self.grid = grid;
+                self.queue = file.pieces.to_ascii_uppercase();
+                (format!("Loaded slot {}", self.slot), GREEN)
+            }
+            Err(e) => (format!("Load failed: {}", e), RED),
+        });
+    }
+
+    /// Creates a practice game starting from the edited board and queue.
+    ///
+    /// # Arguments
+    /// * `difficulty` - The selected difficulty, setting the fall speed
+    pub fn start_practice(&self, difficulty: Difficulty) -> Engine {
+        let sequence = parse_pieces(&self.queue).unwrap_or_default();
+        Engine::from_setup(self.grid, &sequence, difficulty)
+    }
+
+    /// Draws the board being edited, the palette, tools and hints.
+    pub fn draw(&self) {
+        draw_grid(GRAY);
+        draw_grid_blocks(&self.grid);
+
+        draw_text("Editor", panel_x(), 30.0, 32.0, ORANGE);
+
+        // Palette: the piece colors, then garbage; the selected brush is outlined
+        for brush in 0..=COLORS.len() {
+            let (x, swatch_y) = swatch_position(brush);
+            let color = COLORS.get(brush).copied().unwrap_or(GARBAGE_COLOR);
+            draw_rectangle(x, swatch_y, SWATCH_SIZE - 4.0, SWATCH_SIZE - 4.0, color);
+            if brush == self.brush {
+                draw_rectangle_lines(x - 2.0, swatch_y - 2.0, SWATCH_SIZE, SWATCH_SIZE, 2.0, WHITE);
+            }
+        }
+        let mut y = PALETTE_Y + 2.0 * SWATCH_SIZE + 24.0;
+
+        // Tools
+        for tool in Tool::ALL {
+            let (symbol, color) = if tool == self.tool { ("> ", BLUE) } else { ("  ", WHITE) };
+            draw_text(&format!("{}{}", symbol, tool.name()), panel_x(), y, FONT_SIZE, color);
+            y += FONT_SIZE + 2.0;
+        }
+        y += 10.0;
+
+        // Queue, wrapped to the panel width
+        draw_text("Queue:", panel_x(), y, FONT_SIZE, WHITE);
+        y += FONT_SIZE + 2.0;
+        let queue = if self.queue.is_empty() { "(bag)" } else { &self.queue };
+        for chunk in queue.as_bytes().chunks(12) {
+            draw_text(&String::from_utf8_lossy(chunk), panel_x(), y, FONT_SIZE, YELLOW);
+            y += FONT_SIZE + 2.0;
+        }
+        y += 10.0;
+
+        draw_text(&format!("Slot: {}", self.slot), panel_x(), y, FONT_SIZE, SKYBLUE);
+        y += FONT_SIZE + 2.0;
+        if let Some((message, color)) = &self.message {
+            draw_text(message, panel_x(), y, HINT_FONT_SIZE, *color);
+        }
+
+        // Key hints at the bottom of the panel
+        let hints = [
+            "L click: tool",
+            "R click: erase",
+            "Left/Right: brush",
+            "Up/Down: tool",
+            "IOSZJLT: queue",
+            "Del: clear board",
+            "PgUp/PgDn: slot",
+            "F2 save  F3 load",
+            "Enter: practice",
+            "Tab: menu",
+        ];
+        let mut y = GRID_HEIGHT as f32 * block_size() - hints.len() as f32 * (HINT_FONT_SIZE + 2.0);
+        for hint in hints {
+            draw_text(hint, panel_x(), y, HINT_FONT_SIZE, GRAY);
+            y += HINT_FONT_SIZE + 2.0;
+        }
+    }
+}
+
+/// Returns the top-left corner of a palette swatch.
+fn swatch_position(brush: usize) -> (f32, f32) {
+    let column = (brush % SWATCH_COLUMNS) as f32;
+    let row = (brush / SWATCH_COLUMNS) as f32;
+    (panel_x() + column * SWATCH_SIZE, PALETTE_Y + row * SWATCH_SIZE)
+}
+
+/// Returns the brush of the palette swatch under the given screen position, if any.
+fn swatch_at(x: f32, y: f32) -> Option<usize> {
+    (0..=COLORS.len()).find(|&brush| {
+        let (swatch_x, swatch_y) = swatch_position(brush);
+        x >= swatch_x && x < swatch_x + SWATCH_SIZE && y >= swatch_y && y < swatch_y + SWATCH_SIZE
+    })
+}
+
+/// Moves an index by `step` within `0..len`, wrapping around.
+fn cycle(idx: usize, step: i32, len: usize) -> usize {
+    (idx as i32 + step).rem_euclid(len as i32) as usize
+}
@@ -0,0 +1,1155 @@
+// This module defines the Engine, which owns the complete state of a single game in progress.
+//
+// Principles and design choices:
+//
+// - The Engine groups the grid, the active tetromino, the bag, the timers and the game info that
+//   previously lived as loose variables in the main loop, so a game can be created or reset in one call.
+// - Movement is exposed through small methods (try_move, try_rotate, hard_drop) so the main loop keeps
+//   ownership of input handling and the engine stays independent of key bindings.
+// - update() advances the play time, gravity and level progression and reports how the game ended, if it did.
+// - Mode-specific rules (see GameMode) hook into piece locking and spawning.
+// - Garbage-based modes own a GarbageGenerator and the engine keeps count of the garbage rows, which
+//   always form the bottom of the stack: inserting garbage adds to the count and every full row among
+//   them at a clear is dug out. Garbage is never told apart by its color, so any piece color is safe.
+//   Dig refills dug rows when the mode regenerates garbage.
+// - Rising garbage pushes one row in from the bottom on a timer that shortens after every row; the
+//   active tetromino is lifted out of the way, and the panel warns shortly before the next row.
+// - Pieces come from a PieceSet, which provides each piece's shape, color, spawn position and bag weight,
+//   and are dealt by a PieceQueue: a weighted bag, or the fixed sequence of a puzzle.
+// - The player can hold the active piece once per lock (in modes that allow hold); the held piece
+//   swaps back in at the spawn position, and is dealt last when a fixed sequence runs out.
+// - Perfect clear drills play a dealt sequence on an empty grid with the puzzle clear-all goal.
+// - Master mode replaces the fall timer with the MasterState: gravity in cells per frame (up to 20G),
+//   a lock delay reset whenever the piece steps down, and an entry delay with no active piece between
+//   a lock and the next spawn.
+// - Classic mode takes its gravity, scoring and level progression from the classic module and deals
+//   pieces with the NES randomizer.
+// - Practice keeps a Snapshot of the engine (grid, queue, active and held piece, game info) before
+//   every placement, so undo restores the previous placement exactly; gravity can be switched off and
+//   the next piece chosen at will. Without gravity, pieces lock on a hard drop or a soft drop against
+//   the stack.
+// - Practice and puzzles also record a Frame (a Snapshot plus the active piece and fall timer) at the
+//   start of every frame in a bounded ring buffer; rewinding pops frames one at a time, and play resumes
+//   from whichever frame was restored last.
+// - The combo trainer plays in a 4-wide well carved out of the grid by the combo module, always with
+//   the standard tetrominoes the well is sized for; the walls are refilled after every lock. Every mode
+//   counts combos (consecutive line clearing pieces) in GameInfo.
+// - Openers deal the pieces of their steps in order; every lock is checked against the target cells of
+//   the current step, which are drawn as a translucent overlay, and a wrong placement fails the game.
+// - The main loop reports every left, right and rotate key press; at lock the engine compares them with
+//   the fewest inputs reaching the same place (see the finesse module), counts faults and flashes a warning.
+// - T-spins are detected with the three-corner rule when a T locks right after a rotation.
+// - Positions stay in grid cells; in Big mode every move, kick and spawn is multiplied by the
+//   piece scale so tetrominoes always move in whole big-block steps.
+
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+use macroquad::rand::gen_range;
+
+use crate::game::{
+    GarbageGenerator, GarbageHoles, Grid, add_garbage, cascade_blocks, check_collision, clear_lines,
+    count_full_bottom_rows, draw_grid_blocks, lift_tetromino, remove_bottom_rows, stack_tetromino,
+};
+use crate::classic;
+use crate::combo;
+use crate::finesse;
+use crate::game_info::GameInfo;
+use crate::grid::draw_grid;
+use crate::master::{FRAME_RATE, MasterState};
+use crate::mode::GameMode;
+use crate::opener::{Opener, OpenerStep};
+use crate::parameters::{Difficulty, GRID_HEIGHT, GRID_WIDTH, Timers, block_size};
+use crate::perfect_clear::PerfectClearDrill;
+use crate::piece_set::{PieceDef, PieceSet};
+use crate::puzzle::{Puzzle, PuzzleGoal};
+use crate::settings;
+use crate::state::Outcome;
+use crate::tetromino::{
+    NesRandomizer, PieceQueue, TetrominoBag, TetrominoShape, draw_tetromino, rotate,
+};
+
+/// Seconds between automatic level increases.
+const LEVEL_INTERVAL: f32 = 30.0;
+
+/// Factor applied to the rising garbage interval after every row.
+const RISE_SPEEDUP: f32 = 0.95;
+
+/// Shortest interval (in seconds) between rising garbage rows.
+const RISE_MIN_DELAY: f32 = 0.75;
+
+/// Seconds before a garbage row rises during which the incoming-row warning is shown.
+const RISE_WARNING: f32 = 1.0;
+
+/// Most placements kept for undo.
+const UNDO_LIMIT: usize = 200;
+
+/// Seconds the finesse fault warning stays on screen.
+const FAULT_FLASH: f32 = 0.6;
+
+/// Most frames kept for rewind (about 10 seconds at 60 frames per second).
+const REWIND_FRAMES: usize = 600;
+
+/// State of the engine before a placement, restored by undo.
+#[derive(Clone)]
+struct Snapshot {
+    grid: Grid,
+    queue: PieceQueue,
+    piece_idx: usize,
+    hold: Option<usize>,
+    can_hold: bool,
+    game_info: GameInfo,
+    level: usize,
+    fall_delay: f32,
+    pieces_locked: u32,
+}
+
+/// State of the engine at the start of a frame, restored by rewind.
+#[derive(Clone)]
+struct Frame {
+    snapshot: Snapshot,
+    shape: TetrominoShape,
+    color: Color,
+    grid_x: i32,
+    grid_y: i32,
+    last_move_was_rotation: bool,
+    fall_timer: f32,
+}
+
+/// Struct holding the state of a game in progress.
+pub struct Engine {
+    pub mode: GameMode,
+    pub grid: Grid,
+    pieces: PieceSet,
+    queue: PieceQueue,
+    piece_idx: usize,
+    hold: Option<usize>,
+    can_hold: bool,
+    last_move_was_rotation: bool,
+    pub shape: TetrominoShape,
+    pub color: Color,
+    pub grid_x: i32,
+    pub grid_y: i32,
+    pub scale: i32,
+    pub timers: Timers,
+    pub game_info: GameInfo,
+    fall_timer: f32,
+    base_fall_delay: f32,
+    fall_delay: f32,
+    level_timer: f32,
+    level: usize,
+    goal_reached: bool,
+    garbage: Option<GarbageGenerator>,
+    /// Garbage rows left at the bottom of the stack.
+    garbage_rows: usize,
+    rise_timer: f32,
+    rise_delay: f32,
+    master: Option<MasterState>,
+    puzzle_goal: Option<PuzzleGoal>,
+    opener: Option<Vec<OpenerStep>>,
+    pieces_locked: u32,
+    gravity: bool,
+    /// Left, right and rotate key presses spent on the active tetromino.
+    inputs: u32,
+    fault_flash: f32,
+    history: Vec<Snapshot>,
+    frames: VecDeque<Frame>,
+}
+
+/// Implementation of Engine methods.
+impl Engine {
+    /// Creates a new game with an empty grid and a fresh bag.
+    ///
+    /// # Arguments
+    /// * `mode` - The game mode to play
+    /// * `difficulty` - The selected difficulty, setting the initial fall speed
+    /// * `pieces` - The piece set to deal from
+    pub fn new(mode: GameMode, difficulty: Difficulty, pieces: PieceSet) -> Self {
+        let pieces = if mode.has_fixed_pieces() { PieceSet::standard() } else { pieces };
+        // Use TetrominoBag for bag of 7 system, weighted by the piece set (Classic uses the NES randomizer)
+        let queue = match mode {
+            GameMode::Classic { .. } => PieceQueue::Nes(NesRandomizer::new(pieces.pieces.len())),
+            _ => PieceQueue::Bag(TetrominoBag::new(&pieces.weights())),
+        };
+        let mut engine = Self::blank(mode, difficulty, pieces, queue);
+
+        // Classic starts at the selected level, with the NES gravity table
+        if let Some(start_level) = mode.start_level() {
+            engine.level = start_level as usize;
+            engine.fall_delay = classic::fall_delay(engine.level);
+            engine.game_info.set_level(engine.level);
+        }
+
+        // Dig mode starts on top of its garbage rows
+        if let GameMode::Dig { rows, messiness, .. } = mode {
+            let mut generator = GarbageGenerator::new(cheese_holes(messiness));
+            add_garbage(&mut engine.grid, &mut generator, rows as usize);
+            engine.garbage_rows = rows as usize;
+            engine.game_info.garbage_left = Some(rows as usize);
+            engine.garbage = Some(generator);
+        }
+
+        // Survival starts on an empty grid; garbage rises on a timer
+        if let GameMode::Survival { messiness, .. } = mode {
+            engine.garbage = Some(GarbageGenerator::new(cheese_holes(messiness)));
+            engine.game_info.rise_progress = Some(0.0);
+        }
+
+        // The combo trainer starts in a 4-wide well with a random residue
+        if mode.has_combo_well() {
+            engine.grid = combo::start_grid();
+        }
+
+        // Practice shows the gravity switch
+        if mode.has_undo() {
+            engine.show_gravity();
+        }
+
+        // Master starts at the lowest grade
+        if let Some(master) = &engine.master {
+            engine.game_info.grade = Some(master.grade(0, 0.0));
+        }
+
+        engine.spawn();
+        engine
+    }
+
+    /// Creates the daily challenge: standard pieces at Medium speed, dealt from a bag seeded with the
+    /// day, so everyone gets the same pieces without reseeding the game's global random generator.
+    ///
+    /// # Arguments
+    /// * `seed` - The seed of the day
+    pub fn from_daily(seed: u64) -> Self {
+        let pieces = PieceSet::standard();
+        let queue = PieceQueue::Bag(TetrominoBag::seeded(&pieces.weights(), seed));
+        let mut engine = Self::blank(GameMode::Daily, Difficulty::Medium, pieces, queue);
+        engine.spawn();
+        engine
+    }
+
+    /// Creates a game from a puzzle: its board, fixed piece sequence, hold piece and goal.
+    ///
+    /// # Arguments
+    /// * `puzzle` - The puzzle to play
+    /// * `difficulty` - The selected difficulty, setting the fall speed
+    pub fn from_puzzle(puzzle: &Puzzle, difficulty: Difficulty) -> Self {
+        let queue = PieceQueue::Sequence {
+            pieces: puzzle.sequence.clone(),
+            index: 0,
+        };
+        let mut engine = Self::blank(GameMode::Puzzle, difficulty, PieceSet::standard(), queue);
+        engine.grid = puzzle.grid;
+        engine.puzzle_goal = Some(puzzle.goal);
+        engine.game_info.goal_text = Some(puzzle.goal.description());
+        if let Some(hold) = puzzle.hold {
+            engine.set_hold(hold);
+        }
+        engine.spawn();
+        engine
+    }
+
+    /// Creates an opener drill: an empty grid, the pieces of the opener's steps in order and the
+    /// target of the first step.
+    ///
+    /// # Arguments
+    /// * `opener` - The opener to drill
+    /// * `difficulty` - The selected difficulty, setting the fall speed
+    pub fn from_opener(opener: &Opener, difficulty: Difficulty) -> Self {
+        let queue = PieceQueue::Sequence {
+            pieces: opener.sequence(),
+            index: 0,
+        };
+        let mut engine = Self::blank(GameMode::Opener, difficulty, PieceSet::standard(), queue);
+        engine.opener = Some(opener.steps.clone());
+        engine.show_step();
+        engine.spawn();
+        engine
+    }
+
+    /// Creates a perfect clear drill: an empty grid, the dealt pieces in order and a clear-all goal.
+    ///
+    /// # Arguments
+    /// * `drill` - The drill to play
+    /// * `difficulty` - The selected difficulty, setting the fall speed
+    pub fn from_perfect_clear(drill: &PerfectClearDrill, difficulty: Difficulty) -> Self {
+        let queue = PieceQueue::Sequence {
+            pieces: drill.sequence.clone(),
+            index: 0,
+        };
+        let mode = GameMode::PerfectClear { pieces: drill.sequence.len() as u32 };
+        let mut engine = Self::blank(mode, difficulty, PieceSet::standard(), queue);
+        engine.puzzle_goal = Some(PuzzleGoal::ClearAll);
+        engine.game_info.goal_text = Some(format!("Perfect clear: {} lines", drill.lines));
+        engine.spawn();
+        engine
+    }
+
+    /// Creates a practice game from an edited setup: a starting grid and the first pieces to deal,
+    /// followed by the usual bag.
+    ///
+    /// # Arguments
+    /// * `grid` - The starting grid
+    /// * `sequence` - The first pieces, as indices into the standard piece set
+    /// * `difficulty` - The selected difficulty, setting the fall speed
+    pub fn from_setup(grid: Grid, sequence: &[usize], difficulty: Difficulty) -> Self {
+        let pieces = PieceSet::standard();
+        let queue = PieceQueue::Scripted {
+            pieces: sequence.to_vec(),
+            index: 0,
+            bag: TetrominoBag::new(&pieces.weights()),
+        };
+        let mut engine = Self::blank(GameMode::Practice, difficulty, pieces, queue);
+        engine.grid = grid;
+        engine.show_gravity();
+        engine.spawn();
+        engine
+    }
+
+    /// Creates an engine with an empty grid and no active piece yet; callers set up the grid
+    /// and then call spawn() to deal the first piece.
+    fn blank(mode: GameMode, difficulty: Difficulty, pieces: PieceSet, queue: PieceQueue) -> Self {
+        let first = pieces.pieces[0].clone();
+        let mut game_info = GameInfo::new(first.shape, first.color);
+        game_info.line_goal = mode.line_goal();
+        game_info.time_limit = mode.time_limit();
+        game_info.show_time = mode.shows_time();
+        game_info.show_splits = mode.has_splits();
+        game_info.show_hold = mode.has_hold();
+        game_info.show_combo = mode.has_combo_well();
+        let fall_delay = if mode.is_ranked() {
+            difficulty.fall_delay()
+        } else {
+            settings::current().fall_delay(difficulty)
+        };
+
+        Self {
+            mode,
+            grid: [[None; GRID_WIDTH]; GRID_HEIGHT],
+            pieces,
+            queue,
+            piece_idx: 0,
+            hold: None,
+            can_hold: true,
+            last_move_was_rotation: false,
+            shape: first.shape,
+            color: first.color,
+            grid_x: 0,
+            grid_y: 0,
+            scale: mode.piece_scale(),
+            timers: Timers::default(),
+            game_info,
+            fall_timer: 0.0,
+            base_fall_delay: fall_delay,
+            fall_delay,
+            level_timer: 0.0,
+            level: 1,
+            goal_reached: false,
+            garbage: None,
+            garbage_rows: 0,
+            rise_timer: 0.0,
+            rise_delay: mode.rise_interval().unwrap_or(0.0),
+            master: (mode == GameMode::Master).then(MasterState::new),
+            puzzle_goal: None,
+            opener: None,
+            pieces_locked: 0,
+            gravity: true,
+            inputs: 0,
+            fault_flash: 0.0,
+            history: Vec::new(),
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Advances the play time, level progression and gravity by one frame.
+    ///
+    /// # Arguments
+    /// * `dt` - The time elapsed since the last frame, in seconds
+    /// # Returns
+    /// * The outcome of the game if it ended during this frame, `None` otherwise.
+    pub fn update(&mut self, dt: f32) -> Option<Outcome> {
+        // Record the frame for rewind before anything moves
+        if self.mode.has_rewind() {
+            if self.frames.len() == REWIND_FRAMES {
+                self.frames.pop_front();
+            }
+            self.frames.push_back(self.frame());
+        }
+
+        self.game_info.time += dt as f64;
+        self.fault_flash = (self.fault_flash - dt).max(0.0);
+
+        // Time limit: the game ends cleanly when the clock runs out
+        if let Some(limit) = self.mode.time_limit()
+            && self.game_info.time >= limit
+        {
+            self.game_info.time = limit;
+            return Some(Outcome::Completed);
+        }
+
+        // Level progression logic
+        self.level_timer += dt;
+        if self.mode.has_level_progression() && self.level_timer > LEVEL_INTERVAL {
+            self.level_timer = 0.0;
+            self.level += 1;
+            self.fall_delay = (self.fall_delay * 0.9).max(0.1);
+            self.game_info.set_level(self.level);
+        }
+
+        // Master mode has its own gravity, lock delay and entry delay
+        if self.master.is_some() {
+            return self.update_master(dt);
+        }
+
+        // Rising garbage
+        if let Some(outcome) = self.update_rise(dt) {
+            return Some(outcome);
+        }
+
+        // Falling logic (gravity can be switched off in practice)
+        if !self.gravity {
+            return None;
+        }
+        self.fall_timer += dt;
+        if self.fall_timer > self.fall_delay {
+            self.fall_timer = 0.0;
+            if !self.try_move(0, 1) {
+                return self.lock_piece();
+            }
+        }
+        None
+    }
+
+    /// Moves the active tetromino by (dx, dy) blocks if the destination is free.
+    /// In Big mode one block covers several cells, so the move is scaled accordingly.
+    ///
+    /// # Returns
+    /// * `true` if the tetromino moved, `false` if it was blocked.
+    pub fn try_move(&mut self, dx: i32, dy: i32) -> bool {
+        let (dx, dy) = (dx * self.scale, dy * self.scale);
+        if self.waiting_for_spawn()
+            || check_collision(&self.grid, &self.shape, self.grid_x + dx, self.grid_y + dy, self.scale)
+        {
+            return false;
+        }
+        self.grid_x += dx;
+        self.grid_y += dy;
+        self.last_move_was_rotation = false;
+        // Stepping down resets the lock delay
+        if dy > 0
+            && let Some(master) = self.master.as_mut()
+        {
+            master.lock_timer = 0.0;
+        }
+        true
+    }
+
+    /// Moves the active tetromino down one row. In modes with a lock delay, or with gravity switched
+    /// off, a piece already resting on the stack locks at once.
+    ///
+    /// # Returns
+    /// * The outcome of the game if the lock ended it, `None` otherwise.
+    pub fn soft_drop(&mut self) -> Option<Outcome> {
+        if self.try_move(0, 1) {
+            if let Some(master) = self.master.as_mut() {
+                master.soft_rows += 1;
+            }
+            return None;
+        }
+        if (self.master.is_some() || !self.gravity) && !self.waiting_for_spawn() {
+            return self.lock_piece();
+        }
+        None
+    }
+
+    /// Rotates the active tetromino clockwise, trying simple wall kicks near borders or blocks.
+    pub fn try_rotate(&mut self) {
+        if self.waiting_for_spawn() {
+            return;
+        }
+        let rotated = rotate(&self.shape);
+
+        // Try rotating in place
+        if !check_collision(&self.grid, &rotated, self.grid_x, self.grid_y, self.scale) {
+            self.shape = rotated;
+            self.last_move_was_rotation = true;
+            return;
+        }
+        if !self.mode.has_wall_kicks() {
+            return;
+        }
+        // Try wall kicks: left/right by 1 or 2 cells
+        let kicks = [-1, 1, -2, 2];
+        for &kick in &kicks {
+            let dx = kick * self.scale;
+            if !check_collision(&self.grid, &rotated, self.grid_x + dx, self.grid_y, self.scale) {
+                self.shape = rotated;
+                self.grid_x += dx;
+                self.last_move_was_rotation = true;
+                break;
+            }
+        }
+        // If none of the kicks work, do not rotate
+    }
+
+    /// Drops the active tetromino straight down as far as it can go. Without gravity nothing else
+    /// would ever lock it, so it locks at once.
+    ///
+    /// # Returns
+    /// * The outcome of the game if the lock ended it, `None` otherwise.
+    pub fn hard_drop(&mut self) -> Option<Outcome> {
+        while self.try_move(0, 1) {}
+        if !self.gravity && !self.waiting_for_spawn() {
+            return self.lock_piece();
+        }
+        None
+    }
+
+    /// Swaps the active tetromino with the held one (or the next one if nothing is held yet).
+    /// Only allowed once per locked piece, and only in modes with hold.
+    ///
+    /// # Returns
+    /// * `Some(Outcome::ToppedOut)` if the swapped-in tetromino collides immediately, `None` otherwise.
+    pub fn hold(&mut self) -> Option<Outcome> {
+        if !self.mode.has_hold() || !self.can_hold || self.waiting_for_spawn() {
+            return None;
+        }
+        let current = self.piece_idx;
+        match self.hold {
+            Some(held) => {
+                self.set_hold(current);
+                self.set_active(held);
+            }
+            // Holding with an empty hold slot takes the next piece, if there is one
+            None if self.queue.peek().is_some() => {
+                self.set_hold(current);
+                if let Some(outcome) = self.spawn() {
+                    return Some(outcome);
+                }
+            }
+            None => return None,
+        }
+        self.can_hold = false;
+        if check_collision(&self.grid, &self.shape, self.grid_x, self.grid_y, self.scale) {
+            return Some(Outcome::ToppedOut);
+        }
+        None
+    }
+
+    /// Stacks the active tetromino, clears lines and spawns the next one.
+    ///
+    /// # Returns
+    /// * The outcome if the line goal was reached or the new tetromino collides immediately, `None` otherwise.
+    fn lock_piece(&mut self) -> Option<Outcome> {
+        if self.mode.has_undo() {
+            if self.history.len() == UNDO_LIMIT {
+                self.history.remove(0);
+            }
+            self.history.push(self.snapshot());
+        }
+
+        let t_spin = self.is_t_spin();
+        let on_target = self.on_target();
+        let fault = self.judge_finesse();
+        stack_tetromino(&mut self.grid, &self.shape, self.grid_x, self.grid_y, self.color, self.scale);
+        let garbage_cleared = count_full_bottom_rows(&self.grid, self.garbage_rows);
+        self.pieces_locked += 1;
+        self.can_hold = true;
+
+        // Openers stop at the first placement that misses its target, and finesse drills at the first fault
+        if !on_target || (fault && self.mode.restarts_on_fault()) {
+            return Some(Outcome::Failed);
+        }
+
+        // Line clearing and scoring; in cascade mode every clear lets blocks fall and may chain
+        let mut chain = 0;
+        let mut total_cleared = 0;
+        loop {
+            let lines_cleared = clear_lines(&mut self.grid) as u32;
+            if lines_cleared == 0 {
+                break;
+            }
+            total_cleared += lines_cleared;
+            chain += 1;
+            if self.master.is_some() {
+                // Master mode scores the whole lock at once below
+                self.game_info.lines_cleared += lines_cleared;
+            } else if self.mode.start_level().is_some() {
+                self.game_info.score += classic::line_score(lines_cleared, self.level);
+                self.game_info.lines_cleared += lines_cleared;
+            } else if chain == 1 {
+                self.game_info.add_score(lines_cleared);
+            } else {
+                self.game_info.add_chain_score(lines_cleared, chain);
+            }
+            if !self.mode.has_cascade() || !cascade_blocks(&mut self.grid) {
+                break;
+            }
+        }
+        self.game_info.count_combo(total_cleared > 0);
+
+        // The walls of the combo well never run out
+        if self.mode.has_combo_well() {
+            combo::fill_walls(&mut self.grid);
+        }
+
+        // Garbage dug out, refilled when the mode regenerates it
+        if let Some(outcome) = self.update_garbage(garbage_cleared) {
+            return Some(outcome);
+        }
+
+        // Master scoring, level and grade; the next piece spawns after the entry delay
+        if let Some(master) = self.master.as_mut() {
+            let bravo = total_cleared > 0 && self.grid.iter().flatten().all(|cell| cell.is_none());
+            self.game_info.score += master.on_lock(total_cleared, bravo, self.game_info.time);
+            self.game_info.grade = Some(master.grade(self.game_info.score, self.game_info.time));
+            self.game_info.section_times = master.section_times.clone();
+            self.game_info.set_level(master.level);
+            self.game_info.level_goal = Some(master.section_target());
+            if master.finished() {
+                return Some(Outcome::Completed);
+            }
+            let timings = master.timings();
+            let clear_delay = if total_cleared > 0 { timings.line_clear } else { 0.0 };
+            master.entry_delay = Some(timings.entry + clear_delay);
+            return None;
+        }
+
+        // Puzzle goal
+        if let Some(goal) = self.puzzle_goal {
+            let solved = match goal {
+                PuzzleGoal::ClearAll => self.grid.iter().flatten().all(|cell| cell.is_none()),
+                PuzzleGoal::ClearLines { lines } => self.game_info.lines_cleared >= lines,
+                PuzzleGoal::TSpin { lines } => t_spin && total_cleared == lines,
+                PuzzleGoal::Survive { pieces } => self.pieces_locked >= pieces,
+            };
+            if solved {
+                return Some(Outcome::Completed);
+            }
+        }
+
+        // Opener finished once every step is placed
+        if let Some(steps) = &self.opener {
+            if self.pieces_locked as usize >= steps.len() {
+                return Some(Outcome::Completed);
+            }
+            self.show_step();
+        }
+
+        // Line-driven level progression: one level every 10 lines, up to the cap
+        if let Some(cap) = self.mode.line_level_cap() {
+            let level = (1 + self.game_info.lines_cleared as usize / 10).min(cap);
+            if level != self.level {
+                self.level = level;
+                self.fall_delay = (self.base_fall_delay * 0.9f32.powi(level as i32 - 1)).max(0.1);
+                self.game_info.set_level(level);
+            }
+        }
+
+        // Classic level progression with the NES gravity table
+        if let Some(start_level) = self.mode.start_level() {
+            let level = classic::level_for_lines(start_level, self.game_info.lines_cleared);
+            if level != self.level {
+                self.level = level;
+                self.fall_delay = classic::fall_delay(level);
+                self.game_info.set_level(level);
+            }
+        }
+
+        // Splits and line goal (the time stops as soon as the last line clears)
+        self.game_info.record_splits();
+        if !self.goal_reached
+            && self
+                .mode
+                .line_goal()
+                .is_some_and(|goal| self.game_info.lines_cleared >= goal)
+        {
+            self.goal_reached = true;
+            self.game_info.goal_reached = true;
+            if !self.mode.continues_after_goal() {
+                return Some(Outcome::Completed);
+            }
+        }
+
+        // Topping out after the goal was cleared (endless continuation) still counts as cleared
+        match self.spawn() {
+            Some(_) if self.goal_reached => Some(Outcome::Completed),
+            outcome => outcome,
+        }
+    }
+
+    /// Captures the state needed to undo the placement of the active tetromino.
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            grid: self.grid,
+            queue: self.queue.clone(),
+            piece_idx: self.piece_idx,
+            hold: self.hold,
+            can_hold: self.can_hold,
+            game_info: self.game_info.clone(),
+            level: self.level,
+            fall_delay: self.fall_delay,
+            pieces_locked: self.pieces_locked,
+        }
+    }
+
+    /// Restores the state before the last placement, with its tetromino back at the spawn position.
+    ///
+    /// # Returns
+    /// * `true` if a placement was undone, `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.history.pop() else {
+            return false;
+        };
+        let time = self.game_info.time;
+        let piece_idx = snapshot.piece_idx;
+        self.restore(snapshot);
+        self.game_info.time = time;
+        self.set_active(piece_idx);
+        // Frames recorded after the undone placement no longer lead to the current state
+        self.frames.clear();
+        true
+    }
+
+    /// Captures the state of the current frame for rewind.
+    fn frame(&self) -> Frame {
+        Frame {
+            snapshot: self.snapshot(),
+            shape: self.shape,
+            color: self.color,
+            grid_x: self.grid_x,
+            grid_y: self.grid_y,
+            last_move_was_rotation: self.last_move_was_rotation,
+            fall_timer: self.fall_timer,
+        }
+    }
+
+    /// Steps back to the state at the start of the previous recorded frame, including the play time
+    /// and the exact position of the active tetromino.
+    ///
+    /// # Returns
+    /// * `true` if a frame was restored, `false` if there is nothing left to rewind.
+    pub fn rewind(&mut self) -> bool {
+        let Some(frame) = self.frames.pop_back() else {
+            return false;
+        };
+        // Placements made after the restored frame can no longer be undone
+        let pieces_locked = frame.snapshot.pieces_locked;
+        while self.history.last().is_some_and(|s| s.pieces_locked >= pieces_locked) {
+            self.history.pop();
+        }
+        self.restore(frame.snapshot);
+        self.shape = frame.shape;
+        self.color = frame.color;
+        self.grid_x = frame.grid_x;
+        self.grid_y = frame.grid_y;
+        self.last_move_was_rotation = frame.last_move_was_rotation;
+        self.fall_timer = frame.fall_timer;
+        self.timers = Timers::default();
+        true
+    }
+
+    /// Restores the grid, queue, hold, game info and counters of a snapshot, leaving the position
+    /// of the active tetromino to the caller.
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.grid = snapshot.grid;
+        self.queue = snapshot.queue;
+        self.piece_idx = snapshot.piece_idx;
+        self.hold = snapshot.hold;
+        self.can_hold = snapshot.can_hold;
+        self.game_info = snapshot.game_info;
+        self.level = snapshot.level;
+        self.fall_delay = snapshot.fall_delay;
+        self.pieces_locked = snapshot.pieces_locked;
+        // The gravity switch is not part of the game state
+        if self.mode.has_undo() {
+            self.show_gravity();
+        }
+    }
+
+    /// Switches gravity on or off; without gravity the tetromino only moves when the player moves it.
+    pub fn toggle_gravity(&mut self) {
+        self.gravity = !self.gravity;
+        self.show_gravity();
+    }
+
+    /// Shows whether gravity is on in the side panel.
+    fn show_gravity(&mut self) {
+        let text = if self.gravity { "Gravity: on" } else { "Gravity: off" };
+        self.game_info.goal_text = Some(text.to_owned());
+    }
+
+    /// Makes the given piece of the piece set the next one to be dealt.
+    ///
+    /// # Arguments
+    /// * `idx` - The index of the piece in the piece set; out-of-range indices are ignored
+    pub fn choose_next(&mut self, idx: usize) {
+        let Some(piece) = self.pieces.pieces.get(idx) else {
+            return;
+        };
+        self.game_info.set_next(piece.shape, piece.color);
+        self.queue.set_next(idx);
+    }
+
+    /// Returns the step of the opener to place next, if any.
+    fn opener_step(&self) -> Option<&OpenerStep> {
+        self.opener.as_ref()?.get(self.pieces_locked as usize)
+    }
+
+    /// Whether the active tetromino covers exactly the target cells of the current opener step.
+    /// Games without an opener are always on target.
+    fn on_target(&self) -> bool {
+        self.opener_step().is_none_or(|step| self.active_cells() == step.cells)
+    }
+
+    /// Returns the cells covered by the active tetromino, sorted by row then column.
+    fn active_cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for row in 0..self.shape.size() {
+            for col in 0..self.shape.size() {
+                if self.shape[row][col] != 0 {
+                    cells.push(((self.grid_y + row as i32) as usize, (self.grid_x + col as i32) as usize));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Counts a left, right or rotate key press towards the finesse of the active tetromino.
+    pub fn count_input(&mut self) {
+        self.inputs += 1;
+    }
+
+    /// Judges the finesse of the active tetromino about to lock, counting and flashing a fault
+    /// when more inputs were used than needed. Placements out of reach of a hard drop from the
+    /// spawn height (tucks, spins, soft drop slides) are skipped and not counted as judged pieces.
+    ///
+    /// # Returns
+    /// * `true` if the placement is a finesse fault, `false` otherwise (or if it cannot be judged).
+    fn judge_finesse(&mut self) -> bool {
+        if !self.mode.has_finesse() {
+            return false;
+        }
+        let piece = &self.pieces.pieces[self.piece_idx];
+        let Some(min) = finesse::min_inputs(
+            &self.grid,
+            piece.shape,
+            spawn_x(piece, self.scale),
+            piece.spawn_y,
+            self.mode.has_wall_kicks(),
+            &self.active_cells(),
+        ) else {
+            return false;
+        };
+        self.game_info.finesse_pieces += 1;
+        if self.inputs <= min {
+            return false;
+        }
+        self.game_info.finesse_faults += 1;
+        self.fault_flash = FAULT_FLASH;
+        true
+    }
+
+    /// Shows the opener step to place next in the side panel.
+    fn show_step(&mut self) {
+        if let Some(steps) = &self.opener {
+            let text = format!("Step {}/{}", self.pieces_locked as usize + 1, steps.len());
+            self.game_info.goal_text = Some(text);
+        }
+    }
+
+    /// Whether locking the active tetromino now would be a T-spin: the piece is the T,
+    /// its last successful move was a rotation, and at least 3 of the 4 cells diagonal to
+    /// its center are filled or outside the grid.
+    fn is_t_spin(&self) -> bool {
+        if !self.last_move_was_rotation || self.pieces.pieces[self.piece_idx].name != "T" {
+            return false;
+        }
+        // The center of the T is the block with three filled neighbours
+        let size = self.shape.size();
+        let filled = |row: i32, col: i32| {
+            row >= 0
+                && col >= 0
+                && (row as usize) < size
+                && (col as usize) < size
+                && self.shape[row as usize][col as usize] != 0
+        };
+        let Some((row, col)) = (0..size as i32)
+            .flat_map(|row| (0..size as i32).map(move |col| (row, col)))
+            .find(|&(row, col)| {
+                filled(row, col)
+                    && [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                        .iter()
+                        .filter(|(dr, dc)| filled(row + dr, col + dc))
+                        .count()
+                        == 3
+            })
+        else {
+            return false;
+        };
+
+        let corners = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+        let occupied = corners
+            .iter()
+            .filter(|(dr, dc)| {
+                let x = self.grid_x + (col + dc) * self.scale;
+                let y = self.grid_y + (row + dr) * self.scale;
+                x < 0
+                    || x >= GRID_WIDTH as i32
+                    || y >= GRID_HEIGHT as i32
+                    || (y >= 0 && self.grid[y as usize][x as usize].is_some())
+            })
+            .count();
+        occupied >= 3
+    }
+
+    /// Updates the garbage counters after a lock and refills garbage in dig survival.
+    ///
+    /// # Arguments
+    /// * `garbage_cleared` - The number of garbage rows removed by the line clear
+    /// # Returns
+    /// * The outcome if all garbage was dug out or refilled garbage topped out the stack, `None` otherwise.
+    fn update_garbage(&mut self, garbage_cleared: usize) -> Option<Outcome> {
+        self.garbage_rows -= garbage_cleared;
+        let GameMode::Dig { rows, regenerate, .. } = self.mode else {
+            return None;
+        };
+        let generator = self.garbage.as_mut()?;
+        let mut left = self.garbage_rows;
+        self.game_info.garbage_dug += garbage_cleared as u32;
+
+        if regenerate && left < rows as usize {
+            let topped_out = add_garbage(&mut self.grid, generator, rows as usize - left);
+            left = rows as usize;
+            self.garbage_rows = left;
+            if topped_out {
+                self.game_info.garbage_left = Some(left);
+                return Some(Outcome::ToppedOut);
+            }
+        }
+        self.game_info.garbage_left = Some(left);
+        if !regenerate && left == 0 {
+            return Some(Outcome::Completed);
+        }
+        None
+    }
+
+    /// Advances a Master game: the entry delay between pieces, gravity and the lock delay.
+    ///
+    /// # Returns
+    /// * The outcome of the game if it ended during this frame, `None` otherwise.
+    fn update_master(&mut self, dt: f32) -> Option<Outcome> {
+        let master = self.master.as_mut()?;
+
+        // No active piece until the entry delay runs out
+        if let Some(delay) = master.entry_delay {
+            if delay > dt {
+                master.entry_delay = Some(delay - dt);
+                return None;
+            }
+            master.entry_delay = None;
+            return self.spawn();
+        }
+
+        // Gravity: move down whole cells as the fraction accumulates (20G drops to the bottom)
+        master.gravity_progress += master.gravity() * dt * FRAME_RATE;
+        let cells = master.gravity_progress as u32;
+        master.gravity_progress -= cells as f32;
+        for _ in 0..cells {
+            if !self.try_move(0, 1) {
+                break;
+            }
+        }
+
+        // Lock delay while resting on the stack
+        let resting = check_collision(&self.grid, &self.shape, self.grid_x, self.grid_y + 1, self.scale);
+        let master = self.master.as_mut()?;
+        if resting {
+            master.gravity_progress = 0.0;
+            master.lock_timer += dt;
+            if master.lock_timer >= master.timings().lock {
+                return self.lock_piece();
+            }
+        }
+        None
+    }
+
+    /// Whether the engine is between pieces (Master mode entry delay), with no active tetromino.
+    fn waiting_for_spawn(&self) -> bool {
+        self.master.as_ref().is_some_and(|master| master.entry_delay.is_some())
+    }
+
+    /// Advances the rising garbage timer and pushes a garbage row in when it expires,
+    /// lifting the active tetromino above the new row.
+    ///
+    /// # Returns
+    /// * `Some(Outcome::ToppedOut)` if the stack or the active tetromino was pushed out of the top.
+    fn update_rise(&mut self, dt: f32) -> Option<Outcome> {
+        self.mode.rise_interval()?;
+        let generator = self.garbage.as_mut()?;
+        self.rise_timer += dt;
+        if self.rise_timer >= self.rise_delay {
+            self.rise_timer = 0.0;
+            self.rise_delay = (self.rise_delay * RISE_SPEEDUP).max(RISE_MIN_DELAY);
+            let topped_out = add_garbage(&mut self.grid, generator, 1);
+            self.garbage_rows = (self.garbage_rows + 1).min(GRID_HEIGHT);
+            let lifted_out =
+                lift_tetromino(&self.grid, &self.shape, self.grid_x, &mut self.grid_y, self.scale);
+            self.game_info.garbage_risen += 1;
+            if topped_out || lifted_out {
+                return Some(Outcome::ToppedOut);
+            }
+        }
+        self.game_info.rise_progress = Some(self.rise_timer / self.rise_delay);
+        self.game_info.rise_warning = self.rise_imminent();
+        None
+    }
+
+    /// Whether the next rising garbage row is about to arrive.
+    fn rise_imminent(&self) -> bool {
+        self.mode.rise_interval().is_some() && self.rise_delay - self.rise_timer <= RISE_WARNING
+    }
+
+    /// Spawns the next tetromino from the queue at the top of the grid.
+    /// In modes without top out, bottom rows are removed until the new tetromino fits.
+    ///
+    /// # Returns
+    /// * `Some(Outcome::ToppedOut)` if the new tetromino collides immediately,
+    ///   `Some(Outcome::Failed)` if a fixed sequence ran out of pieces, `None` otherwise.
+    fn spawn(&mut self) -> Option<Outcome> {
+        // Once a fixed sequence runs out, the held piece is the last one to play
+        let Some(idx) = self.queue.next().or_else(|| self.take_hold()) else {
+            return Some(Outcome::Failed);
+        };
+        if let Some(master) = self.master.as_mut() {
+            master.on_spawn();
+            self.game_info.set_level(master.level);
+            self.game_info.level_goal = Some(master.section_target());
+        }
+        self.set_active(idx);
+        match self.queue.peek() {
+            Some(next_idx) => {
+                let next = &self.pieces.pieces[next_idx];
+                self.game_info.set_next(next.shape, next.color);
+            }
+            None => self.game_info.clear_next(),
+        }
+
+        if !self.mode.has_top_out() {
+            let mut removed = 0;
+            while removed < GRID_HEIGHT
+                && check_collision(&self.grid, &self.shape, self.grid_x, self.grid_y, self.scale)
+            {
+                remove_bottom_rows(&mut self.grid, 1);
+                removed += 1;
+            }
+        }
+        if check_collision(&self.grid, &self.shape, self.grid_x, self.grid_y, self.scale) {
+            return Some(Outcome::ToppedOut);
+        }
+        None
+    }
+
+    /// Makes the given piece the active tetromino, at its spawn position.
+    fn set_active(&mut self, idx: usize) {
+        let piece = &self.pieces.pieces[idx];
+        self.piece_idx = idx;
+        self.shape = piece.shape;
+        self.color = piece.color;
+        self.grid_x = spawn_x(piece, self.scale);
+        self.grid_y = piece.spawn_y * self.scale;
+        self.fall_timer = 0.0;
+        self.last_move_was_rotation = false;
+        self.inputs = 0;
+    }
+
+    /// Puts the given piece in the hold slot and shows it in the side panel.
+    fn set_hold(&mut self, idx: usize) {
+        let piece = &self.pieces.pieces[idx];
+        self.hold = Some(idx);
+        self.game_info.hold = Some((piece.shape, piece.color));
+    }
+
+    /// Empties the hold slot and returns the piece it held.
+    fn take_hold(&mut self) -> Option<usize> {
+        self.game_info.hold = None;
+        self.hold.take()
+    }
+
+    /// Draws the grid lines and the stacked blocks.
+    pub fn draw_playfield(&self) {
+        let settings = settings::current();
+        if settings.show_grid {
+            draw_grid(settings.grid_color);
+        }
+        draw_grid_blocks(&self.grid);
+
+        // Translucent target of the current opener step
+        if let Some(step) = self.opener_step() {
+            let color = self.pieces.pieces[step.piece].color;
+            for &(row, col) in &step.cells {
+                draw_rectangle(
+                    col as f32 * block_size(),
+                    row as f32 * block_size(),
+                    block_size() - 2.0,
+                    block_size() - 2.0,
+                    Color::new(color.r, color.g, color.b, 0.3),
+                );
+            }
+        }
+
+        // Red frame while a finesse fault is shown
+        if self.fault_flash > 0.0 && settings.finesse_warning {
+            draw_rectangle_lines(
+                0.0,
+                0.0,
+                GRID_WIDTH as f32 * block_size(),
+                GRID_HEIGHT as f32 * block_size(),
+                6.0,
+                RED,
+            );
+            draw_text("Finesse!", 10.0, 60.0, 30.0, RED);
+        }
+
+        // Flash the bottom row while a garbage row is about to rise
+        if self.rise_imminent() && (self.game_info.time * 8.0) as i64 % 2 == 0 {
+            draw_rectangle(
+                0.0,
+                (GRID_HEIGHT - 1) as f32 * block_size(),
+                GRID_WIDTH as f32 * block_size(),
+                block_size(),
+                Color::new(1.0, 0.0, 0.0, 0.35),
+            );
+        }
+    }
+
+    /// Draws the active tetromino.
+    pub fn draw_active(&self) {
+        if self.waiting_for_spawn() {
+            return;
+        }
+        draw_tetromino(&self.shape, self.grid_x, self.grid_y, self.color, self.scale);
+    }
+}
+
+/// Returns the column where a piece spawns: its configured column, or the shape matrix centered on the grid.
+/// The result is a multiple of the scale so big pieces stay aligned to big-block columns.
+///
+/// # Arguments
+/// * `piece` - The piece being spawned
+/// * `scale` - The number of cells covered by each block along each axis
+fn spawn_x(piece: &PieceDef, scale: i32) -> i32 {
+    piece.spawn_column(GRID_WIDTH as i32 / scale) * scale
+}
+
+/// Returns where the holes of cheese garbage go: a single well for clean cheese, a new random
+/// column on every row for fully messy cheese, and a hole that moves now and then in between.
+///
+/// # Arguments
+/// * `messiness` - The chance, in percent, that a row's hole moves away from the previous one
+fn cheese_holes(messiness: u32) -> GarbageHoles {
+    match messiness {
+        0 => GarbageHoles::Same(gen_range(0, GRID_WIDTH)),
+        100.. => GarbageHoles::Random,
+        percent => GarbageHoles::Messy(percent),
+    }
+}
@@ -0,0 +1,224 @@
+// This module computes finesse: the fewest inputs needed to bring a piece from its spawn position to
+// the place where it was dropped, so the engine can tell when the player used more inputs than needed.
+//
+// Principles and design choices:
+//
+// - The inputs are the ones the game offers: a tap left or right moves one cell, holding left or right
+//   (DAS) moves to the wall or the stack, and a rotation turns clockwise with the mode's wall kicks.
+//   Each counts as one input; drops are free.
+// - A breadth-first search over (rotation, column) at the spawn height finds the fewest inputs whose
+//   hard drop covers the same cells as the locked piece, so symmetric pieces (I, S, Z, O) are judged by
+//   where they land rather than by their orientation.
+// - Placements that a hard drop from the spawn height cannot reach (tucks, spins, soft drop slides)
+//   have no minimum and are never counted as faults.
+
+use std::collections::VecDeque;
+
+use crate::game::{Grid, check_collision};
+use crate::parameters::GRID_WIDTH;
+use crate::tetromino::{TetrominoShape, rotate};
+
+/// Wall kick offsets tried when a rotation is blocked, in the order the engine tries them.
+const KICKS: [i32; 4] = [-1, 1, -2, 2];
+
+/// Returns the fewest inputs needed to hard drop a piece on the given cells.
+///
+/// # Arguments
+/// * `grid` - The grid the piece is dropped on
+/// * `shape` - The piece's shape at spawn
+/// * `x` - The spawn column
+/// * `y` - The spawn row
+/// * `wall_kicks` - Whether blocked rotations may shift the piece sideways
+/// * `target` - The cells the piece locked on, as (row, column) sorted by row then column
+/// # Returns
+/// * The number of inputs, or `None` if no hard drop from the spawn height lands on the target.
+pub fn min_inputs(
+    grid: &Grid,
+    shape: TetrominoShape,
+    x: i32,
+    y: i32,
+    wall_kicks: bool,
+    target: &[(usize, usize)],
+) -> Option<u32> {
+    let mut shapes = [shape; 4];
+    for i in 1..4 {
+        shapes[i] = rotate(&shapes[i - 1]);
+    }
+    let fits = |rotation: usize, x: i32| !check_collision(grid, &shapes[rotation], x, y, 1);
+    if !fits(0, x) {
+        return None;
+    }
+
+    // Columns are offset so that shapes hanging past the left wall still index the visited table
+    let offset = 4;
+    let mut visited = [[false; GRID_WIDTH + 8]; 4];
+    let mut queue = VecDeque::from([(0, x, 0)]);
+    visited[0][(x + offset) as usize] = true;
+
+    while let Some((rotation, x, inputs)) = queue.pop_front() {
+        if dropped_cells(grid, &shapes[rotation], x, y) == target {
+            return Some(inputs);
+        }
+
+        let mut moves = Vec::new();
+        for step in [-1, 1] {
+            // Tap: one cell
+            if fits(rotation, x + step) {
+                moves.push((rotation, x + step));
+            }
+            // DAS: as far as the piece goes
+            let mut far = x;
+            while fits(rotation, far + step) {
+                far += step;
+            }
+            moves.push((rotation, far));
+        }
+        // Clockwise rotation, in place or kicked
+        let next = (rotation + 1) % 4;
+        let kicks: &[i32] = if wall_kicks { &KICKS } else { &[] };
+        if let Some(dx) = std::iter::once(&0).chain(kicks).find(|&&dx| fits(next, x + dx)) {
+            moves.push((next, x + dx));
+        }
+
+        for (rotation, x) in moves {
+            let seen = &mut visited[rotation][(x + offset) as usize];
+            if !*seen {
+                *seen = true;
+                queue.push_back((rotation, x, inputs + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Returns the cells covered by a shape hard dropped from (x, y), sorted by row then column.
+fn dropped_cells(grid: &Grid, shape: &TetrominoShape, x: i32, y: i32) -> Vec<(usize, usize)> {
+    let mut y = y;
+    while !check_collision(grid, shape, x, y + 1, 1) {
+        y += 1;
+    }
+    let mut cells = Vec::new();
+    for row in 0..shape.size() {
+        for col in 0..shape.size() {
+            if shape[row][col] != 0 {
+                cells.push(((y + row as i32) as usize, (x + col as i32) as usize));
+            }
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GARBAGE_COLOR;
+    use crate::parameters::GRID_HEIGHT;
+    use crate::tetromino::SHAPES;
+
+    /// Names of the SHAPES, for failure messages.
+    const NAMES: [&str; 7] = ["I", "O", "S", "Z", "J", "L", "T"];
+
+    /// Spawn column of the standard pieces (centered 4x4 boxes).
+    const SPAWN_X: i32 = 3;
+
+    /// Returns the cells a shape covers when resting on the floor of an empty grid, with the left
+    /// edge of its box in column `x`, sorted by row then column.
+    fn on_floor(shape: &TetrominoShape, x: i32) -> Vec<(usize, usize)> {
+        let filled: Vec<(usize, usize)> = (0..shape.size())
+            .flat_map(|row| (0..shape.size()).map(move |col| (row, col)))
+            .filter(|&(row, col)| shape[row][col] != 0)
+            .collect();
+        let lowest = filled.iter().map(|c| c.0).max().unwrap();
+        filled
+            .iter()
+            .map(|&(row, col)| (GRID_HEIGHT - 1 - lowest + row, (x + col as i32) as usize))
+            .collect()
+    }
+
+    /// Returns the first and last columns of a shape's box that hold cells.
+    fn columns(shape: &TetrominoShape) -> (i32, i32) {
+        let cols: Vec<i32> = (0..shape.size())
+            .flat_map(|row| (0..shape.size()).filter(move |&col| shape[row][col] != 0))
+            .map(|col| col as i32)
+            .collect();
+        (*cols.iter().min().unwrap(), *cols.iter().max().unwrap())
+    }
+
+    /// Returns the fewest inputs for a standard piece spawned at the top, with wall kicks.
+    fn inputs(grid: &Grid, piece: usize, target: &[(usize, usize)]) -> Option<u32> {
+        min_inputs(grid, SHAPES[piece], SPAWN_X, 0, true, target)
+    }
+
+    #[test]
+    fn flat_pieces_in_the_centre() {
+        let grid: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+        for (piece, shape) in SHAPES.iter().enumerate() {
+            let name = NAMES[piece];
+            assert_eq!(inputs(&grid, piece, &on_floor(shape, SPAWN_X)), Some(0), "{} at spawn", name);
+            assert_eq!(inputs(&grid, piece, &on_floor(shape, SPAWN_X + 1)), Some(1), "{} one right", name);
+            assert_eq!(inputs(&grid, piece, &on_floor(shape, SPAWN_X - 1)), Some(1), "{} one left", name);
+            assert_eq!(inputs(&grid, piece, &on_floor(shape, SPAWN_X + 2)), Some(2), "{} two right", name);
+        }
+    }
+
+    #[test]
+    fn flat_pieces_at_the_walls() {
+        let grid: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+        for (piece, shape) in SHAPES.iter().enumerate() {
+            let (first, last) = columns(shape);
+            let left = on_floor(shape, -first);
+            let right = on_floor(shape, GRID_WIDTH as i32 - 1 - last);
+            assert_eq!(inputs(&grid, piece, &left), Some(1), "{} at the left wall", NAMES[piece]);
+            assert_eq!(inputs(&grid, piece, &right), Some(1), "{} at the right wall", NAMES[piece]);
+            // One cell off the wall: DAS then a tap back
+            let near_left = on_floor(shape, 1 - first);
+            assert_eq!(inputs(&grid, piece, &near_left), Some(2), "{} next to the left wall", NAMES[piece]);
+        }
+    }
+
+    #[test]
+    fn rotated_pieces() {
+        let grid: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+        let vertical_i = rotate(&SHAPES[0]);
+        let (first, last) = columns(&vertical_i);
+        // The box turns in place, so the vertical I lands two columns right of the box edge
+        assert_eq!(inputs(&grid, 0, &on_floor(&vertical_i, SPAWN_X)), Some(1));
+        assert_eq!(inputs(&grid, 0, &on_floor(&vertical_i, SPAWN_X - 1)), Some(2));
+        assert_eq!(inputs(&grid, 0, &on_floor(&vertical_i, -first)), Some(2));
+        assert_eq!(inputs(&grid, 0, &on_floor(&vertical_i, GRID_WIDTH as i32 - 1 - last)), Some(2));
+
+        // T pointing down at either wall: DAS and two rotations
+        let t_down = rotate(&rotate(&SHAPES[6]));
+        let (first, last) = columns(&t_down);
+        assert_eq!(inputs(&grid, 6, &on_floor(&t_down, SPAWN_X)), Some(2));
+        assert_eq!(inputs(&grid, 6, &on_floor(&t_down, -first)), Some(3));
+        assert_eq!(inputs(&grid, 6, &on_floor(&t_down, GRID_WIDTH as i32 - 1 - last)), Some(3));
+    }
+
+    #[test]
+    fn symmetric_pieces_are_judged_by_where_they_land() {
+        let grid: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+        // An I or O turned twice covers the same cells as at spawn: no input needed, not two rotations
+        for piece in [0, 1] {
+            let turned = rotate(&rotate(&SHAPES[piece]));
+            let target = on_floor(&turned, SPAWN_X);
+            assert_eq!(target, on_floor(&SHAPES[piece], SPAWN_X), "{}", NAMES[piece]);
+            assert_eq!(inputs(&grid, piece, &target), Some(0), "{}", NAMES[piece]);
+        }
+    }
+
+    #[test]
+    fn placements_out_of_hard_drop_reach_have_no_minimum() {
+        let mut grid: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+        // A roof over the bottom left corner: a T can only get under it by sliding in
+        for cell in &mut grid[GRID_HEIGHT - 3][..5] {
+            *cell = Some(GARBAGE_COLOR);
+        }
+        let tucked = on_floor(&SHAPES[6], -columns(&SHAPES[6]).0);
+        assert_eq!(inputs(&grid, 6, &tucked), None);
+        // A spawn position blocked by the stack cannot be judged either
+        let mut full: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+        full[1] = [Some(GARBAGE_COLOR); GRID_WIDTH];
+        assert_eq!(inputs(&full, 6, &on_floor(&SHAPES[6], SPAWN_X)), None);
+    }
+}
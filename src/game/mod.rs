@@ -4,20 +4,29 @@
 //
 // - Defines the Grid type as a 2D array representing the playfield, where each cell is either empty or holds a color.
 // - Implements collision detection for tetromino placement, ensuring pieces do not overlap existing blocks or boundaries.
+// - Collision and stacking take a scale factor so each tetromino block can cover scale x scale cells (Big mode).
 // - Handles stacking of tetrominoes into the grid, updating the playfield state with the correct color.
 // - Provides efficient line clearing logic, removing filled rows and shifting above rows down.
+// - Supports cascade (sticky) gravity, where connected groups of blocks fall as units after a clear.
 // - Includes a function to draw all stacked blocks in the grid for rendering.
+// - Provides a garbage subsystem that pushes rows onto the bottom of the grid, shifting the stack up and
+//   lifting the active tetromino out of the way; the hole column stays the same, is picked at random,
+//   or moves away from the previous hole with a messiness percentage.
 // - All logic is designed for clarity, efficiency, and compatibility with the standard Tetris rules.
 
 use macroquad::prelude::*;
+use macroquad::rand::gen_range;
 
-use crate::parameters::{BLOCK_SIZE, GRID_HEIGHT, GRID_WIDTH};
+use crate::parameters::{GRID_HEIGHT, GRID_WIDTH, block_size};
 use crate::tetromino::TetrominoShape;
 
 /// Type alias for the Tetris grid, a 2D array of optional Colors.
 /// Each cell is either None (empty) or Some(Color) (filled with a block of that color).
 pub type Grid = [[Option<Color>; GRID_WIDTH]; GRID_HEIGHT];
 
+/// Color used to draw garbage blocks.
+pub const GARBAGE_COLOR: Color = GRAY;
+
 /// Checks if placing the tetromino at (x, y) would result in a collision with the grid or boundaries.
 ///
 /// # Arguments
@@ -25,19 +34,20 @@ pub type Grid = [[Option<Color>; GRID_WIDTH]; GRID_HEIGHT];
 /// * `shape` - The shape of the tetromino to check.
 /// * `x` - The x position to check (in grid coordinates).
 /// * `y` - The y position to check (in grid coordinates).
+/// * `scale` - The number of cells covered by each block along each axis (1 normally, 2 in Big mode).
 /// # Returns
 /// * `true` if there is a collision, `false` otherwise.
-pub fn check_collision(grid: &Grid, shape: &TetrominoShape, x: i32, y: i32) -> bool {
-    for row in 0..4 {
-        for col in 0..4 {
+pub fn check_collision(grid: &Grid, shape: &TetrominoShape, x: i32, y: i32, scale: i32) -> bool {
+    for row in 0..shape.size() {
+        for col in 0..shape.size() {
             if shape[row][col] != 0 {
-                let nx = x + col as i32;
-                let ny = y + row as i32;
-                if nx < 0 || nx >= GRID_WIDTH as i32 || ny >= GRID_HEIGHT as i32 {
-                    return true;
-                }
-                if ny >= 0 && grid[ny as usize][nx as usize].is_some() {
-                    return true;
+                for (nx, ny) in block_cells(x, y, row, col, scale) {
+                    if nx < 0 || nx >= GRID_WIDTH as i32 || ny >= GRID_HEIGHT as i32 {
+                        return true;
+                    }
+                    if ny >= 0 && grid[ny as usize][nx as usize].is_some() {
+                        return true;
+                    }
                 }
             }
         }
@@ -53,33 +63,48 @@ pub fn check_collision(grid: &Grid, shape: &TetrominoShape, x: i32, y: i32) -> b
 /// * `x` - The x position to stack at (in grid coordinates).
 /// * `y` - The y position to stack at (in grid coordinates).
 /// * `color` - The color of the tetromino.
-pub fn stack_tetromino(grid: &mut Grid, shape: &TetrominoShape, x: i32, y: i32, color: Color) {
-    for row in 0..4 {
-        for col in 0..4 {
+/// * `scale` - The number of cells covered by each block along each axis (1 normally, 2 in Big mode).
+pub fn stack_tetromino(grid: &mut Grid, shape: &TetrominoShape, x: i32, y: i32, color: Color, scale: i32) {
+    for row in 0..shape.size() {
+        for col in 0..shape.size() {
             if shape[row][col] != 0 {
-                let nx = x + col as i32;
-                let ny = y + row as i32;
-                if ny >= 0 && ny < GRID_HEIGHT as i32 && nx >= 0 && nx < GRID_WIDTH as i32 {
-                    grid[ny as usize][nx as usize] = Some(color);
+                for (nx, ny) in block_cells(x, y, row, col, scale) {
+                    if ny >= 0 && ny < GRID_HEIGHT as i32 && nx >= 0 && nx < GRID_WIDTH as i32 {
+                        grid[ny as usize][nx as usize] = Some(color);
+                    }
                 }
             }
         }
     }
 }
 
+/// Returns the grid cells covered by the block at (row, col) of a tetromino placed at (x, y).
+///
+/// # Arguments
+/// * `x` - The x position of the tetromino (in grid coordinates).
+/// * `y` - The y position of the tetromino (in grid coordinates).
+/// * `row` - The row of the block inside the tetromino shape.
+/// * `col` - The column of the block inside the tetromino shape.
+/// * `scale` - The number of cells covered by the block along each axis.
+pub fn block_cells(x: i32, y: i32, row: usize, col: usize, scale: i32) -> impl Iterator<Item = (i32, i32)> {
+    let left = x + col as i32 * scale;
+    let top = y + row as i32 * scale;
+    (0..scale).flat_map(move |dy| (0..scale).map(move |dx| (left + dx, top + dy)))
+}
+
 /// Draws the stacked blocks in the grid.
 ///
 /// # Arguments
 /// * `grid` - The current state of the Tetris grid.
 pub fn draw_grid_blocks(grid: &Grid) {
-    for y in 0..GRID_HEIGHT {
-        for x in 0..GRID_WIDTH {
-            if let Some(color) = grid[y][x] {
+    for (y, row) in grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if let Some(color) = *cell {
                 draw_rectangle(
-                    x as f32 * BLOCK_SIZE,
-                    y as f32 * BLOCK_SIZE,
-                    BLOCK_SIZE - 2.0,
-                    BLOCK_SIZE - 2.0,
+                    x as f32 * block_size(),
+                    y as f32 * block_size(),
+                    block_size() - 2.0,
+                    block_size() - 2.0,
                     color,
                 );
             }
@@ -111,3 +136,396 @@ pub fn clear_lines(grid: &mut Grid) -> usize {
     }
     cleared
 }
+
+/// Removes rows from the bottom of the grid, shifting the rest of the stack down.
+///
+/// # Arguments
+/// * `grid` - The current state of the Tetris grid (mutable).
+/// * `count` - The number of bottom rows to remove.
+pub fn remove_bottom_rows(grid: &mut Grid, count: usize) {
+    let count = count.min(GRID_HEIGHT);
+    for row in (count..GRID_HEIGHT).rev() {
+        grid[row] = grid[row - count];
+    }
+    for row in grid.iter_mut().take(count) {
+        *row = [None; GRID_WIDTH];
+    }
+}
+
+/// Labels each filled cell with the index of the connected group (4-neighbour adjacency) it belongs to.
+///
+/// # Arguments
+/// * `grid` - The current state of the Tetris grid.
+/// # Returns
+/// * A grid of group labels (None for empty cells) and the number of groups found.
+fn label_groups(grid: &Grid) -> ([[Option<usize>; GRID_WIDTH]; GRID_HEIGHT], usize) {
+    let mut labels = [[None; GRID_WIDTH]; GRID_HEIGHT];
+    let mut groups = 0;
+    for y in 0..GRID_HEIGHT {
+        for x in 0..GRID_WIDTH {
+            if grid[y][x].is_none() || labels[y][x].is_some() {
+                continue;
+            }
+            // Flood fill the group starting from this cell
+            let mut stack = vec![(x, y)];
+            labels[y][x] = Some(groups);
+            while let Some((cx, cy)) = stack.pop() {
+                let neighbours = [
+                    (cx.wrapping_sub(1), cy),
+                    (cx + 1, cy),
+                    (cx, cy.wrapping_sub(1)),
+                    (cx, cy + 1),
+                ];
+                for (nx, ny) in neighbours {
+                    if nx < GRID_WIDTH
+                        && ny < GRID_HEIGHT
+                        && grid[ny][nx].is_some()
+                        && labels[ny][nx].is_none()
+                    {
+                        labels[ny][nx] = Some(groups);
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            groups += 1;
+        }
+    }
+    (labels, groups)
+}
+
+/// Applies cascade (sticky) gravity: connected groups of blocks fall as units until they land.
+/// Groups are moved one row at a time, lowest first, so falling groups can land on each other.
+///
+/// # Arguments
+/// * `grid` - The current state of the Tetris grid (mutable).
+/// # Returns
+/// * `true` if any block moved, `false` otherwise.
+pub fn cascade_blocks(grid: &mut Grid) -> bool {
+    let mut moved_any = false;
+    loop {
+        let (mut labels, groups) = label_groups(grid);
+
+        // Order groups by their lowest cell so the bottom ones fall first
+        let mut lowest = vec![0; groups];
+        for (y, row) in labels.iter().enumerate() {
+            for id in row.iter().flatten() {
+                lowest[*id] = y;
+            }
+        }
+        let mut order: Vec<usize> = (0..groups).collect();
+        order.sort_by(|a, b| lowest[*b].cmp(&lowest[*a]));
+
+        let mut moved = false;
+        for id in order {
+            // A group can fall if every cell below it is empty or part of the same group
+            let can_fall = (0..GRID_HEIGHT).all(|y| {
+                (0..GRID_WIDTH).all(|x| {
+                    labels[y][x] != Some(id)
+                        || (y + 1 < GRID_HEIGHT
+                            && (grid[y + 1][x].is_none() || labels[y + 1][x] == Some(id)))
+                })
+            });
+            if !can_fall {
+                continue;
+            }
+            // Move the group down one row, starting from the bottom to avoid overwriting
+            for y in (0..GRID_HEIGHT - 1).rev() {
+                for x in 0..GRID_WIDTH {
+                    if labels[y][x] == Some(id) {
+                        grid[y + 1][x] = grid[y][x];
+                        labels[y + 1][x] = labels[y][x];
+                        grid[y][x] = None;
+                        labels[y][x] = None;
+                    }
+                }
+            }
+            moved = true;
+        }
+        if !moved {
+            break;
+        }
+        moved_any = true;
+    }
+    moved_any
+}
+
+/// Strategy used to choose the hole column of each garbage row.
+///  - Same: Every row has its hole in the given column (a clean well).
+///  - Random: Every row gets an independent random hole.
+///  - Messy: Each row keeps the previous hole, moving it to another random column
+///    with the given percentage chance (0 = clean well, 100 = a new column every row).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GarbageHoles {
+    Same(usize),
+    Random,
+    Messy(u32),
+}
+
+/// Generates garbage rows, remembering the last hole column for messy garbage.
+pub struct GarbageGenerator {
+    holes: GarbageHoles,
+    last_hole: usize,
+}
+
+/// Implementation of GarbageGenerator methods.
+impl GarbageGenerator {
+    pub fn new(holes: GarbageHoles) -> Self {
+        let last_hole = match holes {
+            GarbageHoles::Same(col) => col.min(GRID_WIDTH - 1),
+            _ => gen_range(0, GRID_WIDTH),
+        };
+        Self { holes, last_hole }
+    }
+
+    /// Picks the hole column for the next garbage row.
+    fn next_hole(&mut self) -> usize {
+        let hole = match self.holes {
+            GarbageHoles::Same(col) => col.min(GRID_WIDTH - 1),
+            GarbageHoles::Random => gen_range(0, GRID_WIDTH),
+            GarbageHoles::Messy(percent) => {
+                if gen_range(0, 100) < percent.min(100) {
+                    // Move the hole to any other column
+                    (self.last_hole + gen_range(1, GRID_WIDTH)) % GRID_WIDTH
+                } else {
+                    self.last_hole
+                }
+            }
+        };
+        self.last_hole = hole;
+        hole
+    }
+
+    /// Builds the next garbage row: every cell filled except the hole.
+    pub fn next_row(&mut self) -> [Option<Color>; GRID_WIDTH] {
+        let hole = self.next_hole();
+        let mut row = [Some(GARBAGE_COLOR); GRID_WIDTH];
+        row[hole] = None;
+        row
+    }
+}
+
+/// Pushes `count` garbage rows onto the bottom of the grid, shifting the stack up.
+///
+/// # Arguments
+/// * `grid` - The current state of the Tetris grid (mutable).
+/// * `generator` - The generator deciding where each row's hole goes.
+/// * `count` - The number of garbage rows to insert.
+/// # Returns
+/// * `true` if stacked blocks were pushed out of the top of the grid (top out), `false` otherwise.
+pub fn add_garbage(grid: &mut Grid, generator: &mut GarbageGenerator, count: usize) -> bool {
+    let count = count.min(GRID_HEIGHT);
+    if count == 0 {
+        return false;
+    }
+    let topped_out = grid[..count]
+        .iter()
+        .any(|row| row.iter().any(|cell| cell.is_some()));
+
+    // Shift the stack up, dropping the rows that leave the top
+    grid.copy_within(count.., 0);
+    for row in grid[GRID_HEIGHT - count..].iter_mut() {
+        *row = generator.next_row();
+    }
+    topped_out
+}
+
+/// Counts the full rows among the bottom rows of the grid, which the next line clear removes.
+/// Garbage always sits at the bottom of the stack, so this tells how many garbage rows a clear digs out.
+///
+/// # Arguments
+/// * `grid` - The current state of the Tetris grid.
+/// * `rows` - The number of bottom rows to look at.
+/// # Returns
+/// * The number of full rows among them.
+pub fn count_full_bottom_rows(grid: &Grid, rows: usize) -> usize {
+    grid[GRID_HEIGHT - rows.min(GRID_HEIGHT)..]
+        .iter()
+        .filter(|row| row.iter().all(|cell| cell.is_some()))
+        .count()
+}
+
+/// Lifts the active tetromino until it no longer overlaps the stack.
+/// Used after garbage insertion, which can push blocks into the falling piece.
+///
+/// # Arguments
+/// * `grid` - The current state of the Tetris grid.
+/// * `shape` - The shape of the active tetromino.
+/// * `x` - The x position of the active tetromino (in grid coordinates).
+/// * `y` - The y position of the active tetromino (mutable, in grid coordinates).
+/// * `scale` - The number of cells covered by each block along each axis.
+/// # Returns
+/// * `true` if the tetromino had to be lifted above the top of the grid (top out), `false` otherwise.
+pub fn lift_tetromino(grid: &Grid, shape: &TetrominoShape, x: i32, y: &mut i32, scale: i32) -> bool {
+    let mut lifted = false;
+    while check_collision(grid, shape, x, *y, scale) {
+        *y -= 1;
+        lifted = true;
+    }
+    let top_row = (0..shape.size()).find(|&row| shape[row].iter().any(|&c| c != 0)).unwrap_or(0) as i32;
+    lifted && *y + top_row * scale < 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetromino::SHAPES;
+
+    /// Bottom row of the grid.
+    const BOTTOM: usize = GRID_HEIGHT - 1;
+
+    fn empty() -> Grid {
+        [[None; GRID_WIDTH]; GRID_HEIGHT]
+    }
+
+    /// Returns the empty columns of a row.
+    fn holes(row: &[Option<Color>; GRID_WIDTH]) -> Vec<usize> {
+        (0..GRID_WIDTH).filter(|&x| row[x].is_none()).collect()
+    }
+
+    #[test]
+    fn garbage_rows_stack_from_the_bottom() {
+        let mut grid = empty();
+        let mut generator = GarbageGenerator::new(GarbageHoles::Same(3));
+        assert!(!add_garbage(&mut grid, &mut generator, 4));
+        for row in &grid[GRID_HEIGHT - 4..] {
+            assert_eq!(holes(row), [3]);
+        }
+        assert!(grid[..GRID_HEIGHT - 4].iter().all(|row| holes(row).len() == GRID_WIDTH));
+
+        // The stack moves up with the rows pushed under it
+        grid[GRID_HEIGHT - 5][0] = Some(RED);
+        assert!(!add_garbage(&mut grid, &mut generator, 2));
+        assert_eq!(grid[GRID_HEIGHT - 7][0], Some(RED));
+        assert!(grid[GRID_HEIGHT - 6..].iter().all(|row| holes(row) == [3]));
+        assert!(!add_garbage(&mut grid, &mut generator, 0));
+    }
+
+    #[test]
+    fn hole_strategies() {
+        let mut grid = empty();
+        // A column past the grid is kept on the last one
+        let mut same = GarbageGenerator::new(GarbageHoles::Same(GRID_WIDTH + 5));
+        add_garbage(&mut grid, &mut same, 3);
+        assert!(grid[GRID_HEIGHT - 3..].iter().all(|row| holes(row) == [GRID_WIDTH - 1]));
+
+        // No messiness keeps a single well, full messiness moves the hole on every row
+        let mut clean = GarbageGenerator::new(GarbageHoles::Messy(0));
+        add_garbage(&mut grid, &mut clean, 10);
+        let well = holes(&grid[BOTTOM]);
+        assert_eq!(well.len(), 1);
+        assert!(grid[GRID_HEIGHT - 10..].iter().all(|row| holes(row) == well));
+        let mut messy = GarbageGenerator::new(GarbageHoles::Messy(100));
+        add_garbage(&mut grid, &mut messy, 10);
+        for pair in grid[GRID_HEIGHT - 10..].windows(2) {
+            assert_eq!(holes(&pair[0]).len(), 1);
+            assert_ne!(holes(&pair[0]), holes(&pair[1]));
+        }
+
+        let mut random = GarbageGenerator::new(GarbageHoles::Random);
+        add_garbage(&mut grid, &mut random, GRID_HEIGHT);
+        assert!(grid.iter().all(|row| holes(row).len() == 1));
+    }
+
+    #[test]
+    fn garbage_tops_out_when_the_stack_leaves_the_grid() {
+        let mut grid = empty();
+        let mut generator = GarbageGenerator::new(GarbageHoles::Same(0));
+        grid[2][4] = Some(RED);
+        // Two rows still fit above the block, three push it out of the top
+        assert!(!add_garbage(&mut grid, &mut generator, 2));
+        assert_eq!(grid[0][4], Some(RED));
+        assert!(add_garbage(&mut grid, &mut generator, 1));
+        assert!(grid.iter().flatten().all(|cell| *cell != Some(RED)));
+
+        // More rows than the grid holds fill it and top out
+        let mut grid = empty();
+        grid[BOTTOM][4] = Some(RED);
+        assert!(add_garbage(&mut grid, &mut generator, GRID_HEIGHT + 3));
+        assert!(grid.iter().all(|row| holes(row) == [0]));
+    }
+
+    #[test]
+    fn pushed_piece_is_lifted_out_of_the_stack() {
+        let mut grid = empty();
+        let mut generator = GarbageGenerator::new(GarbageHoles::Same(9));
+        // A T (flat side down, in the second row of its box) resting on the floor
+        let t = &SHAPES[6];
+        let mut y = GRID_HEIGHT as i32 - 2;
+        assert!(!check_collision(&grid, t, 3, y, 1));
+        add_garbage(&mut grid, &mut generator, 2);
+        assert!(check_collision(&grid, t, 3, y, 1));
+        assert!(!lift_tetromino(&grid, t, 3, &mut y, 1));
+        assert_eq!(y, GRID_HEIGHT as i32 - 4);
+        assert!(!check_collision(&grid, t, 3, y, 1));
+
+        // A piece with nothing in the way stays where it is
+        let mut still = 5;
+        assert!(!lift_tetromino(&grid, t, 3, &mut still, 1));
+        assert_eq!(still, 5);
+
+        // A piece lifted past the top of the grid tops out
+        add_garbage(&mut grid, &mut generator, GRID_HEIGHT - 3);
+        let mut y = 0;
+        assert!(lift_tetromino(&grid, t, 3, &mut y, 1));
+        assert!(y < 0);
+    }
+
+    #[test]
+    fn full_bottom_rows_are_counted() {
+        let mut grid = empty();
+        let mut generator = GarbageGenerator::new(GarbageHoles::Same(0));
+        add_garbage(&mut grid, &mut generator, 3);
+        assert_eq!(count_full_bottom_rows(&grid, 3), 0);
+        grid[BOTTOM][0] = Some(RED);
+        grid[BOTTOM - 2][0] = Some(RED);
+        assert_eq!(count_full_bottom_rows(&grid, 3), 2);
+        // Only the given rows are looked at
+        assert_eq!(count_full_bottom_rows(&grid, 1), 1);
+        assert_eq!(count_full_bottom_rows(&grid, GRID_HEIGHT + 1), 2);
+    }
+
+    #[test]
+    fn connected_groups_are_labelled() {
+        let mut grid = empty();
+        grid[BOTTOM][0] = Some(RED);
+        grid[BOTTOM][1] = Some(RED);
+        grid[BOTTOM - 1][1] = Some(RED);
+        // Touching only by a corner is a separate group
+        grid[BOTTOM - 2][2] = Some(RED);
+        grid[BOTTOM][5] = Some(RED);
+        let (labels, groups) = label_groups(&grid);
+        assert_eq!(groups, 3);
+        assert_eq!(labels[BOTTOM][0], labels[BOTTOM - 1][1]);
+        assert_ne!(labels[BOTTOM - 1][1], labels[BOTTOM - 2][2]);
+        assert_eq!(labels[BOTTOM - 1][0], None);
+    }
+
+    #[test]
+    fn floating_group_falls_and_chains_into_a_clear() {
+        let mut grid = empty();
+        // The bottom row waits for column 0, the row above for columns 0 and 1
+        grid[BOTTOM][1..].fill(Some(GARBAGE_COLOR));
+        grid[BOTTOM - 1][2..].fill(Some(GARBAGE_COLOR));
+        // A full row about to clear, with a group resting on it: a column of two in column 0 and a
+        // block beside its top
+        grid[BOTTOM - 2] = [Some(BLUE); GRID_WIDTH];
+        grid[BOTTOM - 4][0] = Some(RED);
+        grid[BOTTOM - 3][0] = Some(RED);
+        grid[BOTTOM - 4][1] = Some(RED);
+
+        // First clear; plain gravity would leave the group hanging over the holes
+        assert_eq!(clear_lines(&mut grid), 1);
+        assert!(grid[BOTTOM - 1][0].is_none());
+
+        // The group falls as one piece into both holes and completes the two rows
+        assert!(cascade_blocks(&mut grid));
+        assert_eq!(grid[BOTTOM][0], Some(RED));
+        assert_eq!(grid[BOTTOM - 1][0], Some(RED));
+        assert_eq!(grid[BOTTOM - 1][1], Some(RED));
+        assert_eq!(clear_lines(&mut grid), 2);
+        assert!(grid.iter().flatten().all(|cell| cell.is_none()));
+
+        // Nothing left to fall
+        assert!(!cascade_blocks(&mut grid));
+    }
+}
@@ -5,6 +5,15 @@
 // Principles and design choices:
 //
 // - The GameInfo struct tracks score, lines cleared, next tetromino shape/color, and level.
+// - Timed modes also use the play time (with millisecond precision), an optional line goal and split
+//   times every SPLIT_LINES lines, compared against the personal best splits when known.
+// - Master mode shows the level against the current section stop, the grade and the section times.
+// - Every mode counts combos (consecutive line clearing pieces); the combo trainer shows the current
+//   and longest combo.
+// - Finesse is counted as the number of judged pieces and faults; the percentage goes in the results.
+// - Rising garbage modes show a meter filling up towards the next incoming row.
+// - Modes with hold show the held tetromino under the level; puzzles show their goal, and the next
+//   preview is left empty once a fixed piece sequence runs out.
 // - The draw() method renders all info in a visually grouped and spaced layout, making it easy
 //   for the player to see their progress and upcoming piece at a glance.
 // - The next tetromino preview is centered in its area, regardless of shape, for a polished look.
@@ -14,26 +23,81 @@
 
 use macroquad::prelude::*;
 
-use crate::parameters::{BLOCK_SIZE, GRID_WIDTH, SCORE_WIDTH};
+use crate::master::{MAX_LEVEL, SECTION_LEVELS};
+use crate::parameters::{GRID_WIDTH, SCORE_WIDTH, block_size};
 use crate::tetromino::TetrominoShape;
+use crate::ui::format_time;
 
 /// Panel layout constants for consistent spacing and positioning of game information.
-const PANEL_X: f32 = GRID_WIDTH as f32 * BLOCK_SIZE + 20.0;
 const PANEL_Y: f32 = 40.0;
 const FONT_SIZE: f32 = 32.0;
 const SECTION_SPACING: f32 = 24.0;
 const SECTION_HEIGHT: f32 = FONT_SIZE + 16.0;
 const NEXT_PREVIEW_WIDTH: f32 = SCORE_WIDTH - 40.0;
 const NEXT_PREVIEW_HEIGHT: f32 = 100.0; // Adjust as needed for your layout
+const HOLD_PREVIEW_HEIGHT: f32 = 40.0;
+const SMALL_FONT_SIZE: f32 = 20.0;
+const SPLITS_SHOWN: usize = 3;
+
+/// Left edge of the panel, right of the grid (which depends on the block size).
+fn panel_x() -> f32 {
+    GRID_WIDTH as f32 * block_size() + 20.0
+}
+
+/// Number of lines between two split times.
+pub const SPLIT_LINES: u32 = 10;
 
 /// Struct to hold and manage game information for the side panel.
 /// Tracks score, lines cleared, next tetromino, and level.
+#[derive(Clone)]
 pub struct GameInfo {
     pub score: u32,
     pub lines_cleared: u32,
     pub next_shape: TetrominoShape,
     pub next_color: Color,
+    /// Whether there is a next tetromino to preview (a puzzle's sequence can run out).
+    pub has_next: bool,
+    /// The held tetromino, shown when the mode allows hold.
+    pub hold: Option<(TetrominoShape, Color)>,
+    pub show_hold: bool,
+    /// Goal or status line of the current game (e.g. a puzzle goal or the daily date).
+    pub goal_text: Option<String>,
     pub level: usize,
+    /// Level that ends the current section, shown as "level/goal".
+    pub level_goal: Option<usize>,
+    /// Grade earned so far, in graded modes.
+    pub grade: Option<&'static str>,
+    /// Time taken by each finished section, in graded modes.
+    pub section_times: Vec<f64>,
+    pub max_chain: u32,
+    /// Consecutive line clearing pieces so far (0 after a piece that cleared nothing).
+    pub combo: u32,
+    pub max_combo: u32,
+    /// Combos of at least 2 clearing pieces.
+    pub combos: u32,
+    pub show_combo: bool,
+    /// Pieces judged for finesse, and those placed with more inputs than needed.
+    pub finesse_pieces: u32,
+    pub finesse_faults: u32,
+    /// Play time in seconds.
+    pub time: f64,
+    pub show_time: bool,
+    /// Time limit in seconds; when set, the panel shows the time left instead of the time played.
+    pub time_limit: Option<f64>,
+    pub line_goal: Option<u32>,
+    pub goal_reached: bool,
+    pub show_splits: bool,
+    /// Garbage rows still on the grid, in garbage-based modes.
+    pub garbage_left: Option<usize>,
+    pub garbage_dug: u32,
+    /// Progress (0 to 1) towards the next rising garbage row, in rising garbage modes.
+    pub rise_progress: Option<f32>,
+    /// Whether the next rising garbage row is about to arrive.
+    pub rise_warning: bool,
+    pub garbage_risen: u32,
+    /// Time at which every SPLIT_LINES lines were reached.
+    pub splits: Vec<f64>,
+    pub best_splits: Vec<f64>,
 }
 
 /// Implementation of GameInfo methods.
@@ -46,10 +110,56 @@ impl GameInfo {
             lines_cleared: 0,
             next_shape,
             next_color,
+            has_next: true,
+            hold: None,
+            show_hold: false,
+            goal_text: None,
             level: 1,
+            level_goal: None,
+            grade: None,
+            section_times: Vec::new(),
+            max_chain: 0,
+            combo: 0,
+            max_combo: 0,
+            combos: 0,
+            show_combo: false,
+            finesse_pieces: 0,
+            finesse_faults: 0,
+            time: 0.0,
+            show_time: false,
+            time_limit: None,
+            line_goal: None,
+            goal_reached: false,
+            show_splits: false,
+            garbage_left: None,
+            garbage_dug: 0,
+            rise_progress: None,
+            rise_warning: false,
+            garbage_risen: 0,
+            splits: Vec::new(),
+            best_splits: Vec::new(),
+        }
+    }
+
+    /// Records a split time for every SPLIT_LINES lines reached since the last split,
+    /// stopping at the line goal.
+    pub fn record_splits(&mut self) {
+        let goal = self.line_goal.unwrap_or(u32::MAX);
+        let reached = self.lines_cleared.min(goal) / SPLIT_LINES;
+        while (self.splits.len() as u32) < reached {
+            self.splits.push(self.time);
         }
     }
 
+    /// Returns the difference between a split and the personal best split, if known.
+    ///
+    /// # Arguments
+    /// * `index` - The index of the split
+    pub fn split_delta(&self, index: usize) -> Option<f64> {
+        let best = self.best_splits.get(index)?;
+        self.splits.get(index).map(|split| split - best)
+    }
+
     /// Adds score and updates lines cleared based on the number of lines just cleared.
     /// 
     /// # Arguments
@@ -59,6 +169,41 @@ impl GameInfo {
         self.lines_cleared += lines;
     }
 
+    /// Adds score for a follow-up clear of a cascade chain; later steps of a chain are worth more.
+    ///
+    /// # Arguments
+    /// * `lines` - The number of lines cleared in this step.
+    /// * `chain` - The position of this step in the chain (2 for the first follow-up clear).
+    pub fn add_chain_score(&mut self, lines: u32, chain: u32) {
+        self.score += 100 * lines * lines * chain;
+        self.lines_cleared += lines;
+        self.max_chain = self.max_chain.max(chain);
+    }
+
+    /// Counts a locked piece towards the current combo.
+    ///
+    /// # Arguments
+    /// * `cleared` - Whether the piece cleared at least one line
+    pub fn count_combo(&mut self, cleared: bool) {
+        if !cleared {
+            self.combo = 0;
+            return;
+        }
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+        if self.combo == 2 {
+            self.combos += 1;
+        }
+    }
+
+    /// Returns the share of judged pieces placed without a finesse fault, in percent.
+    pub fn finesse_percent(&self) -> Option<f64> {
+        (self.finesse_pieces > 0).then(|| {
+            let clean = self.finesse_pieces - self.finesse_faults;
+            100.0 * clean as f64 / self.finesse_pieces as f64
+        })
+    }
+
     /// Sets the next tetromino's shape and color.
     /// 
     /// # Arguments
@@ -67,6 +212,12 @@ impl GameInfo {
     pub fn set_next(&mut self, shape: TetrominoShape, color: Color) {
         self.next_shape = shape;
         self.next_color = color;
+        self.has_next = true;
+    }
+
+    /// Clears the next tetromino preview when no piece follows.
+    pub fn clear_next(&mut self) {
+        self.has_next = false;
     }
 
     /// Sets the current level.
@@ -82,10 +233,10 @@ impl GameInfo {
         let mut y = PANEL_Y;
 
         // Draw score
-        draw_text("Score:", PANEL_X, y, FONT_SIZE, YELLOW);
+        draw_text("Score:", panel_x(), y, FONT_SIZE, YELLOW);
         draw_text(
             &format!("{}", self.score),
-            PANEL_X,
+            panel_x(),
             y + FONT_SIZE + 8.0,
             FONT_SIZE,
             YELLOW,
@@ -93,38 +244,178 @@ impl GameInfo {
         y += SECTION_HEIGHT + SECTION_SPACING;
 
         // Draw lines cleared
-        draw_text("Lines:", PANEL_X, y, FONT_SIZE, GREEN);
+        draw_text("Lines:", panel_x(), y, FONT_SIZE, GREEN);
+        let lines_text = match self.line_goal {
+            Some(goal) => format!("{}/{}", self.lines_cleared, goal),
+            None => format!("{}", self.lines_cleared),
+        };
+        // Lines turn gold once the goal is cleared and play continues
+        let lines_color = if self.goal_reached { GOLD } else { GREEN };
         draw_text(
-            &format!("{}", self.lines_cleared),
-            PANEL_X,
+            &lines_text,
+            panel_x(),
             y + FONT_SIZE + 8.0,
             FONT_SIZE,
-            GREEN,
+            lines_color,
         );
         y += SECTION_HEIGHT + SECTION_SPACING;
 
         // Draw next tetromino label and preview
-        draw_text("Next:", PANEL_X, y, FONT_SIZE, WHITE);
+        draw_text("Next:", panel_x(), y, FONT_SIZE, WHITE);
         // Draw the tetromino below the label, with some extra space
-        draw_next_tetromino(
-            &self.next_shape,
-            self.next_color,
-            PANEL_X,
-            y + FONT_SIZE + 12.0,
-            NEXT_PREVIEW_WIDTH,
-            NEXT_PREVIEW_HEIGHT,
-        );
+        if self.has_next {
+            draw_next_tetromino(
+                &self.next_shape,
+                self.next_color,
+                panel_x(),
+                y + FONT_SIZE + 12.0,
+                NEXT_PREVIEW_WIDTH,
+                NEXT_PREVIEW_HEIGHT,
+            );
+        }
 
         // Draw current level below the next tetromino preview, with spacing
-        let level_text = format!("Level: {}", self.level);
+        let level_text = match self.level_goal {
+            Some(goal) => format!("Level: {}/{}", self.level, goal),
+            None => format!("Level: {}", self.level),
+        };
         let level_y = y + FONT_SIZE + NEXT_PREVIEW_HEIGHT + 32.0; // 32.0 for extra spacing
         draw_text(
             &level_text,
-            PANEL_X,
+            panel_x(),
             level_y,
             FONT_SIZE,
             YELLOW,
         );
+
+        let mut y = level_y + SECTION_HEIGHT;
+
+        // Draw the held tetromino in a compact preview below the level
+        if self.show_hold {
+            let label_y = level_y + SMALL_FONT_SIZE + 8.0;
+            draw_text("Hold:", panel_x(), label_y, SMALL_FONT_SIZE, WHITE);
+            if let Some((shape, color)) = &self.hold {
+                draw_next_tetromino(
+                    shape,
+                    *color,
+                    panel_x(),
+                    label_y + 6.0,
+                    NEXT_PREVIEW_WIDTH,
+                    HOLD_PREVIEW_HEIGHT,
+                );
+            }
+            y = label_y + HOLD_PREVIEW_HEIGHT + SECTION_HEIGHT;
+        }
+
+        // Draw the goal or status line
+        if let Some(goal) = &self.goal_text {
+            draw_text(goal, panel_x(), y, SMALL_FONT_SIZE, GOLD);
+            y += SMALL_FONT_SIZE + 8.0;
+        }
+
+        // Draw the best cascade chain once a chain has happened
+        if self.max_chain > 1 {
+            draw_text(
+                &format!("Chain: {}", self.max_chain),
+                panel_x(),
+                y,
+                FONT_SIZE,
+                ORANGE,
+            );
+            y += SECTION_HEIGHT;
+        }
+
+        // Draw the current and longest combo
+        if self.show_combo {
+            draw_text(&format!("Combo: {}", self.combo), panel_x(), y, FONT_SIZE, ORANGE);
+            draw_text(
+                &format!("Max combo: {}", self.max_combo),
+                panel_x(),
+                y + SMALL_FONT_SIZE + 8.0,
+                SMALL_FONT_SIZE,
+                ORANGE,
+            );
+            y += SECTION_HEIGHT + SMALL_FONT_SIZE;
+        }
+
+        // Draw play time and the most recent splits
+        if self.show_time {
+            let (label, time) = match self.time_limit {
+                Some(limit) => ("Left:", limit - self.time),
+                None => ("Time:", self.time),
+            };
+            draw_text(label, panel_x(), y, FONT_SIZE, SKYBLUE);
+            draw_text(
+                &format_time(time),
+                panel_x(),
+                y + FONT_SIZE + 8.0,
+                FONT_SIZE,
+                SKYBLUE,
+            );
+            y += SECTION_HEIGHT + SECTION_SPACING;
+        }
+        // Draw the garbage rows left to dig and dug so far
+        if let Some(left) = self.garbage_left {
+            draw_text(&format!("Garbage: {}", left), panel_x(), y, SMALL_FONT_SIZE, LIGHTGRAY);
+            draw_text(
+                &format!("Dug: {}", self.garbage_dug),
+                panel_x(),
+                y + SMALL_FONT_SIZE + 2.0,
+                SMALL_FONT_SIZE,
+                LIGHTGRAY,
+            );
+            y += 2.0 * (SMALL_FONT_SIZE + 2.0) + 8.0;
+        }
+
+        // Draw the grade and the most recent section times
+        if let Some(grade) = self.grade {
+            draw_text(&format!("Grade: {}", grade), panel_x(), y, FONT_SIZE, GOLD);
+            y += SECTION_HEIGHT;
+        }
+        let first = self.section_times.len().saturating_sub(SPLITS_SHOWN);
+        for (i, section) in self.section_times.iter().enumerate().skip(first) {
+            draw_text(
+                &format!(
+                    "{:03}: {}",
+                    ((i + 1) * SECTION_LEVELS).min(MAX_LEVEL),
+                    format_time(*section)
+                ),
+                panel_x(),
+                y,
+                SMALL_FONT_SIZE,
+                SKYBLUE,
+            );
+            y += SMALL_FONT_SIZE + 2.0;
+        }
+
+        // Draw the incoming garbage row meter, turning red just before the row rises
+        if let Some(progress) = self.rise_progress {
+            let color = if self.rise_warning { RED } else { LIGHTGRAY };
+            draw_text("Incoming:", panel_x(), y, SMALL_FONT_SIZE, color);
+            let bar_y = y + 8.0;
+            draw_rectangle(panel_x(), bar_y, NEXT_PREVIEW_WIDTH * progress.min(1.0), 10.0, color);
+            draw_rectangle_lines(panel_x(), bar_y, NEXT_PREVIEW_WIDTH, 10.0, 2.0, GRAY);
+            y = bar_y + 10.0 + SMALL_FONT_SIZE + 8.0;
+        }
+
+        if self.show_splits {
+            let first = self.splits.len().saturating_sub(SPLITS_SHOWN);
+            for (i, split) in self.splits.iter().enumerate().skip(first) {
+                let (delta, color) = match self.split_delta(i) {
+                    Some(d) if d <= 0.0 => (format!(" -{:.2}", -d), GREEN),
+                    Some(d) => (format!(" +{:.2}", d), RED),
+                    None => (String::new(), WHITE),
+                };
+                draw_text(
+                    &format!("{}{}", format_time(*split), delta),
+                    panel_x(),
+                    y,
+                    SMALL_FONT_SIZE,
+                    color,
+                );
+                y += SMALL_FONT_SIZE + 2.0;
+            }
+        }
     }
 }
 
@@ -142,12 +433,13 @@ fn draw_next_tetromino(
     area_height: f32,
 ) {
     // Find the bounding box of the tetromino shape
-    let mut min_row = 4;
+    let size = next_shape.size();
+    let mut min_row = size;
     let mut max_row = 0;
-    let mut min_col = 4;
+    let mut min_col = size;
     let mut max_col = 0;
-    for row in 0..4 {
-        for col in 0..4 {
+    for row in 0..size {
+        for col in 0..size {
             if next_shape[row][col] != 0 {
                 if row < min_row {
                     min_row = row;
@@ -164,22 +456,26 @@ fn draw_next_tetromino(
             }
         }
     }
-    let shape_width = (max_col - min_col + 1) as f32 * BLOCK_SIZE;
-    let shape_height = (max_row - min_row + 1) as f32 * BLOCK_SIZE;
+    // Shrink the blocks if a large piece (e.g. a pentomino) would not fit the preview area
+    let columns = (max_col - min_col + 1) as f32;
+    let rows = (max_row - min_row + 1) as f32;
+    let block_size = block_size().min(area_width / columns).min(area_height / rows);
+    let shape_width = columns * block_size;
+    let shape_height = rows * block_size;
 
     // Center the tetromino in the preview area
     let center_x = area_x + (area_width - shape_width) / 2.0;
     let center_y = area_y + (area_height - shape_height) / 2.0;
 
-    // Draw the tetromino shape (4x4 grid)
-    for row in 0..4 {
-        for col in 0..4 {
+    // Draw the tetromino shape
+    for row in 0..size {
+        for col in 0..size {
             if next_shape[row][col] != 0 {
-                let x = center_x + (col - min_col) as f32 * BLOCK_SIZE;
-                let y = center_y + (row - min_row) as f32 * BLOCK_SIZE;
-                draw_rectangle(x, y, BLOCK_SIZE, BLOCK_SIZE, color);
-                draw_rectangle_lines(x, y, BLOCK_SIZE, BLOCK_SIZE, 2.0, BLACK);
+                let x = center_x + (col - min_col) as f32 * block_size;
+                let y = center_y + (row - min_row) as f32 * block_size;
+                draw_rectangle(x, y, block_size, block_size, color);
+                draw_rectangle_lines(x, y, block_size, block_size, 2.0, BLACK);
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -8,7 +8,7 @@
 
 use macroquad::prelude::*;
 
-use crate::parameters::{BLOCK_SIZE, GRID_HEIGHT, GRID_WIDTH};
+use crate::parameters::{GRID_HEIGHT, GRID_WIDTH, block_size};
 
 /// Draws the Tetris grid using lines.
 ///
@@ -18,10 +18,10 @@ pub fn draw_grid(color: Color) {
     for x in 0..GRID_WIDTH {
         for y in 0..GRID_HEIGHT {
             draw_rectangle_lines(
-                x as f32 * BLOCK_SIZE,
-                y as f32 * BLOCK_SIZE,
-                BLOCK_SIZE,
-                BLOCK_SIZE,
+                x as f32 * block_size(),
+                y as f32 * block_size(),
+                block_size(),
+                block_size(),
                 1.0,
                 color,
             );
@@ -0,0 +1,203 @@
+// This module keeps the top-10 high score tables, one per mode (with its options) and difficulty.
+//
+// Principles and design choices:
+//
+// - Every table is identified by a readable name built from the mode, its options and the difficulty
+//   (e.g. "Sprint 40, Off - Medium"), so different line goals or time limits never share a table.
+// - Most modes rank by score; modes racing to a goal (Sprint, Dig) rank completed games by time.
+// - An entry keeps the player's name, the date, the score, lines, level and, in timed modes, the time.
+//   The last name entered is remembered so the next record only needs Enter.
+// - Tables are persisted through the storage module, whose atomic writes keep the file intact even if
+//   the game crashes while saving.
+
+use serde::{Deserialize, Serialize};
+
+use crate::daily;
+use crate::game_info::GameInfo;
+use crate::mode::GameMode;
+use crate::parameters::Difficulty;
+use crate::state::Outcome;
+use crate::storage;
+use crate::ui::format_time;
+
+/// File name of the high scores inside the data directory.
+const HIGH_SCORE_FILE: &str = "highscores.toml";
+
+/// Number of entries kept in each table.
+pub const TABLE_SIZE: usize = 10;
+
+/// Longest name accepted on a new record.
+pub const MAX_NAME_LENGTH: usize = 12;
+
+/// One entry of a high score table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    /// Date of the game as "YYYY-MM-DD".
+    pub date: String,
+    pub score: u32,
+    pub lines: u32,
+    pub level: usize,
+    /// Play time in seconds, in modes that show the time.
+    pub time: Option<f64>,
+}
+
+/// The high score table of one mode and difficulty, best entry first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScoreTable {
+    pub name: String,
+    /// Whether entries are ranked by time (fastest first) rather than by score.
+    pub by_time: bool,
+    #[serde(default)]
+    pub entries: Vec<HighScore>,
+}
+
+/// Implementation of HighScoreTable methods.
+impl HighScoreTable {
+    /// Returns the column headers and widths of the table screen.
+    pub fn columns(&self) -> [(&'static str, f32); 6] {
+        let result = if self.by_time { "Time" } else { "Score" };
+        [("#", 30.0), ("Name", 130.0), (result, 90.0), ("Lines", 55.0), ("Lvl", 40.0), ("Date", 100.0)]
+    }
+
+    /// Returns the cells of every entry, in the order of columns().
+    pub fn rows(&self) -> Vec<Vec<String>> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let result = match entry.time {
+                    Some(time) if self.by_time => format_time(time),
+                    _ => entry.score.to_string(),
+                };
+                vec![
+                    (i + 1).to_string(),
+                    entry.name.clone(),
+                    result,
+                    entry.lines.to_string(),
+                    entry.level.to_string(),
+                    entry.date.clone(),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// All high score tables, and the name entered last.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    #[serde(default)]
+    pub last_name: String,
+    #[serde(default)]
+    pub tables: Vec<HighScoreTable>,
+}
+
+/// Implementation of HighScores methods.
+impl HighScores {
+    /// Loads the high scores from the data directory.
+    pub fn load() -> Self {
+        storage::load(HIGH_SCORE_FILE)
+    }
+
+    /// Saves the high scores to the data directory.
+    pub fn save(&self) -> Result<(), String> {
+        storage::save(HIGH_SCORE_FILE, self)
+    }
+
+    /// Returns the position a finished game would take in its table, if it makes the top 10.
+    ///
+    /// # Arguments
+    /// * `mode` - The mode of the game, with its options
+    /// * `difficulty` - The difficulty of the game
+    /// * `outcome` - How the game ended
+    /// * `info` - The game info of the game
+    /// # Returns
+    /// * The 0-based rank, or `None` if the game is not ranked or does not make the table.
+    pub fn rank(&self, mode: GameMode, difficulty: Difficulty, outcome: Outcome, info: &GameInfo) -> Option<usize> {
+        if !mode.has_high_scores() || (mode.ranks_by_time() && outcome != Outcome::Completed) {
+            return None;
+        }
+        let entry = new_entry(mode, info, String::new());
+        let by_time = mode.ranks_by_time();
+        let entries = self
+            .tables
+            .iter()
+            .find(|table| table.name == table_name(mode, difficulty))
+            .map_or(&[][..], |table| &table.entries[..]);
+        let rank = entries.iter().take_while(|e| !beats(&entry, e, by_time)).count();
+        (rank < TABLE_SIZE).then_some(rank)
+    }
+
+    /// Adds a finished game to its table under the given name and saves the file.
+    ///
+    /// # Arguments
+    /// * `mode` - The mode of the game, with its options
+    /// * `difficulty` - The difficulty of the game
+    /// * `info` - The game info of the game
+    /// * `name` - The player's name
+    /// # Returns
+    /// * An error message if the file could not be written.
+    pub fn submit(&mut self, mode: GameMode, difficulty: Difficulty, info: &GameInfo, name: &str) -> Result<(), String> {
+        let by_time = mode.ranks_by_time();
+        let name_of_table = table_name(mode, difficulty);
+        let idx = match self.tables.iter().position(|table| table.name == name_of_table) {
+            Some(idx) => idx,
+            None => {
+                self.tables.push(HighScoreTable {
+                    name: name_of_table,
+                    by_time,
+                    entries: Vec::new(),
+                });
+                self.tables.len() - 1
+            }
+        };
+        let entry = new_entry(mode, info, name.to_owned());
+        let entries = &mut self.tables[idx].entries;
+        let rank = entries.iter().take_while(|e| !beats(&entry, e, by_time)).count();
+        entries.insert(rank, entry);
+        entries.truncate(TABLE_SIZE);
+        self.last_name = name.to_owned();
+        self.save()
+    }
+
+    /// Returns the index of the table of the given mode and difficulty, if it has been created.
+    pub fn table_index(&self, mode: GameMode, difficulty: Difficulty) -> Option<usize> {
+        let name = table_name(mode, difficulty);
+        self.tables.iter().position(|table| table.name == name)
+    }
+}
+
+/// Returns the name of the table of a mode and difficulty, e.g. "Sprint 40, Off - Medium".
+///
+/// # Arguments
+/// * `mode` - The mode, with its options
+/// * `difficulty` - The difficulty
+pub fn table_name(mode: GameMode, difficulty: Difficulty) -> String {
+    let options: Vec<String> = mode.options().into_iter().map(|(_, value)| value).collect();
+    if options.is_empty() {
+        format!("{} - {}", mode.name(), difficulty.name())
+    } else {
+        format!("{} {} - {}", mode.name(), options.join(", "), difficulty.name())
+    }
+}
+
+/// Builds the entry of a finished game, dated today.
+fn new_entry(mode: GameMode, info: &GameInfo, name: String) -> HighScore {
+    HighScore {
+        name,
+        date: daily::today(),
+        score: info.score,
+        lines: info.lines_cleared,
+        level: info.level,
+        time: mode.shows_time().then_some(info.time),
+    }
+}
+
+/// Whether a new entry ranks strictly above an existing one; ties keep the older entry first.
+fn beats(entry: &HighScore, other: &HighScore, by_time: bool) -> bool {
+    if by_time {
+        entry.time.unwrap_or(f64::MAX) < other.time.unwrap_or(f64::MAX)
+    } else {
+        entry.score > other.score
+    }
+}
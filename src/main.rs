@@ -1,337 +1,1025 @@
 use macroquad::prelude::*;
+use macroquad::rand::srand;
 
+mod bindings;
+mod classic;
+mod combo;
+mod daily;
+mod editor;
+mod engine;
+mod finesse;
 mod game;
 mod game_info;
 mod grid;
+mod high_scores;
+mod master;
+mod opener;
+mod menu;
+mod mode;
 mod parameters;
+mod perfect_clear;
+mod piece_set;
+mod profiles;
+mod puzzle;
+mod records;
+mod settings;
 mod state;
+mod storage;
 mod tetromino;
 mod ui;
 
-use crate::parameters::{
-    BLOCK_SIZE, GRID_HEIGHT, GRID_WIDTH, SCORE_WIDTH, SOFT_DROP_DELAY_HORIZONTAL,
-    SOFT_DROP_DELAY_VERTICAL, Timers, Difficulty
+use crate::parameters::{GRID_HEIGHT, GRID_WIDTH, SCORE_WIDTH, block_size, init_block_size};
+use bindings::{Action, Bindings, Preset};
+use daily::{DailyHistory, DailyResult};
+use editor::Editor;
+use engine::Engine;
+use game_info::SPLIT_LINES;
+use high_scores::{HighScores, MAX_NAME_LENGTH};
+use menu::{ListMenu, StartMenu};
+use mode::GameMode;
+use opener::load_openers;
+use perfect_clear::PerfectClearDrill;
+use piece_set::{PieceSet, load_piece_sets};
+use profiles::{DEFAULT_PROFILE, MAX_PROFILE_NAME, Profiles, valid_char};
+use puzzle::{PuzzleProgress, load_puzzles};
+use records::{SprintRecords, Statistics};
+use settings::Settings;
+use state::{GameState, Outcome};
+use ui::{
+    Results, draw_banner, draw_centered_text, draw_bottom_centered_text, draw_menu_note, draw_name_entry, draw_profile_entry, draw_results,
+    draw_table, format_time,
 };
-use game::{Grid, check_collision, clear_lines, draw_grid_blocks, stack_tetromino};
-use game_info::GameInfo;
-use grid::draw_grid;
-use state::GameState;
-use tetromino::{SHAPES, TetrominoBag, draw_tetromino, rotate};
-use ui::{draw_centered_text, draw_bottom_centered_text, draw_difficulty_menu};
+
+/// Seconds during which a second Quit press quits the game.
+const QUIT_CONFIRM_TIME: f32 = 2.0;
+
+/// Keys that pick the next piece in practice, by position in the piece set.
+const PIECE_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
 
 fn window_conf() -> Conf {
+    // The window size follows the block size of the last profile's settings, fixed for the whole run
+    Profiles::load().activate();
+    init_block_size(Settings::load().0.block_size as f32);
     Conf {
         window_title: "Tetris RS".to_owned(),
-        window_width: (GRID_WIDTH as f32 * BLOCK_SIZE + SCORE_WIDTH) as i32,
-        window_height: (GRID_HEIGHT as f32 * BLOCK_SIZE) as i32,
+        window_width: (GRID_WIDTH as f32 * block_size() + SCORE_WIDTH) as i32,
+        window_height: (GRID_HEIGHT as f32 * block_size()) as i32,
         ..Default::default()
     }
 }
 
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut game_state = GameState::Waiting;
-    let mut grid: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
-
-    let tetromino_colors = [ORANGE, YELLOW, GREEN, RED, BLUE, PURPLE, PINK];
-
-    // Use TetrominoBag for bag of 7 system
-    let mut bag = TetrominoBag::new();
-    let mut shape_idx = bag.next();
-    let mut shape = SHAPES[shape_idx];
-    let mut color = tetromino_colors[shape_idx];
-    let mut next_idx = bag.peek();
-    let mut next_shape = SHAPES[next_idx];
-    let mut next_color = tetromino_colors[next_idx];
-
-    let mut game_info = GameInfo::new(next_shape, next_color);
-
-    let mut grid_x = 3;
-    let mut grid_y = 0;
-    let mut fall_timer = 0.0;
-    let mut timers = Timers::default();
-
-    let mut selected_menu = 1; // 0: Easy, 1: Medium, 2: Hard
-    let difficulties = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
-    let mut difficulty = Difficulty::Medium;
-    let mut fall_delay = difficulty.fall_delay();
-    let mut level_timer = 0.0;
-    let mut level = 1;
+    let mut game_state = GameState::Profiles;
+    srand(miniquad::date::now() as u64);
+
+    // Local profiles; the files below are those of the profile used last until another one is chosen
+    let mut profiles = Profiles::load();
+    profiles.activate();
+    let mut profile_menu = ListMenu::new();
+    profile_menu.selected = profiles.current();
+    let mut creating_profile = false;
+    let mut profile_name = String::new();
+    let mut profile_note: Option<String> = None;
+
+    // Settings from the config directory; problems found in the file are shown on the start menu
+    let (loaded, mut settings_errors) = Settings::load();
+    for e in &settings_errors {
+        eprintln!("Settings: {}", e);
+    }
+    settings::apply(loaded);
+    let mut settings_menu = ListMenu::new();
+    let mut edited_settings = loaded;
+
+    // Key bindings from the config directory, the copy being edited on the controls screen,
+    // whether the next key press is captured, and the time left to confirm quitting
+    let (mut bindings, mut binding_errors) = Bindings::load();
+    for e in &binding_errors {
+        eprintln!("Bindings: {}", e);
+    }
+    let mut controls_menu = ListMenu::new();
+    let mut edited_bindings = bindings.clone();
+    let mut capturing = false;
+    let mut controls_note: Option<(String, Color)> = None;
+    let mut quit_prompt = 0.0;
+
+    // Piece sets: the standard tetrominoes plus any sets found in the pieces directory
+    let piece_sets = load_piece_sets();
+    let mut menu = StartMenu::new(&piece_sets);
+    let mut engine = Engine::new(menu.mode, menu.difficulty, piece_sets[0].clone());
+
+    // Puzzles from the puzzles directory and the player's solved puzzles
+    let puzzles = load_puzzles();
+    let mut puzzle_menu = ListMenu::new();
+    let mut puzzle_progress = PuzzleProgress::load();
+
+    // Openers from the openers directory
+    let openers = load_openers();
+    let mut opener_menu = ListMenu::new();
+
+    // The pieces of the current perfect clear drill, kept for retries
+    let mut pc_drill = PerfectClearDrill::deal(0);
+
+    // Personal records, statistics and the results of the last finished game
+    let mut sprint_records = SprintRecords::load();
+    let mut statistics = Statistics::load();
+    let mut results = Results::new("Game Over", RED);
+
+    // High score tables, the rank and name of a new entry, and the table shown on the high score screen
+    let mut high_scores = HighScores::load();
+    let mut new_rank = 0;
+    let mut name = String::new();
+    let mut high_score_page = 0;
+
+    // Daily challenge history; only the first daily game of a day is ranked
+    let mut daily_history = DailyHistory::load();
+    let mut daily_date = daily::today();
+    let mut daily_ranked = false;
+
+    // Board editor, and whether the current practice game was launched from it
+    let mut editor = Editor::new();
+    let mut practice_from_editor = false;
 
     loop {
-        clear_background(BLACK);
+        let current = settings::current();
+        // Quit keys are typed as text while a name is entered, and Escape cancels a capture on the
+        // controls screen, so none of them quits there
+        let typing = matches!(game_state, GameState::NameEntry(_) | GameState::Controls) || creating_profile;
+        clear_background(current.background);
 
         match game_state {
+            GameState::Profiles if creating_profile => {
+                draw_profile_entry(&profile_name);
+                if let Some(note) = &profile_note {
+                    draw_menu_note(note, RED);
+                }
+
+                // Characters arrive most recent first
+                let mut typed: Vec<char> = std::iter::from_fn(get_char_pressed).collect();
+                typed.reverse();
+                for c in typed {
+                    if valid_char(c) && profile_name.chars().count() < MAX_PROFILE_NAME {
+                        profile_name.push(c);
+                    }
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    profile_name.pop();
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    creating_profile = false;
+                    profile_note = None;
+                }
+                if is_key_pressed(KeyCode::Enter) {
+                    match profiles.create(&profile_name) {
+                        Ok(idx) => {
+                            profile_menu.selected = idx;
+                            creating_profile = false;
+                            profile_note = None;
+                        }
+                        Err(e) => profile_note = Some(e),
+                    }
+                }
+            }
+            GameState::Profiles => {
+                profile_menu.draw_profiles(&profiles);
+                if let Some(note) = &profile_note {
+                    draw_menu_note(note, RED);
+                }
+
+                // The last entry creates a profile; the others switch to it and load its files
+                if profile_menu.update(profiles.names.len() + 1) {
+                    if profile_menu.selected == profiles.names.len() {
+                        profile_name.clear();
+                        clear_input_queue();
+                        creating_profile = true;
+                    } else {
+                        if let Err(e) = profiles.select(profile_menu.selected) {
+                            eprintln!("Could not save profiles: {}", e);
+                        }
+                        let (loaded, errors) = Settings::load();
+                        settings::apply(loaded);
+                        edited_settings = loaded;
+                        settings_errors = errors;
+                        (bindings, binding_errors) = Bindings::load();
+                        edited_bindings = bindings.clone();
+                        high_scores = HighScores::load();
+                        sprint_records = SprintRecords::load();
+                        statistics = Statistics::load();
+                        daily_history = DailyHistory::load();
+                        puzzle_progress = PuzzleProgress::load();
+                        profile_note = None;
+                        game_state = GameState::Waiting;
+                    }
+                }
+            }
             GameState::Waiting => {
-                // Draw difficulty selection menu
-                draw_difficulty_menu(selected_menu);
+                // Draw start menu (mode, difficulty, piece set)
+                menu.draw(&profiles.last);
                 
                 // Draw instruction to start
                 draw_bottom_centered_text("Press Enter to start", 48.0, YELLOW);
+
+                // Tell whether today's daily challenge is still to be played
+                if menu.mode == GameMode::Daily {
+                    match daily_history.result(&daily::today()) {
+                        Some(result) if !result.finished => {
+                            draw_menu_note("Today's attempt was abandoned - practice only", GRAY)
+                        }
+                        Some(result) => draw_menu_note(
+                            &format!("Played today: {} points - practice only", result.score),
+                            GRAY,
+                        ),
+                        None => draw_menu_note("One scored attempt today!", GOLD),
+                    }
+                } else if let Some(error) = settings_errors.first() {
+                    draw_menu_note(&format!("Settings: {}", error), RED);
+                } else if let Some(error) = binding_errors.first() {
+                    draw_menu_note(&format!("Controls: {}", error), RED);
+                } else if let Some(note) = menu.note() {
+                    draw_menu_note(note, GRAY);
+                } else if statistics.games > 0 {
+                    draw_menu_note(&statistics.summary(), GRAY);
+                }
                 
-                // Menu navigation logic
-                if is_key_pressed(KeyCode::Up) && selected_menu > 0 {
-                    selected_menu -= 1;
+                // The board editor opens from the start menu
+                if is_key_pressed(KeyCode::E) {
+                    editor.open();
+                    game_state = GameState::Editor;
                 }
-                if is_key_pressed(KeyCode::Down) && selected_menu < 2 {
-                    selected_menu += 1;
+
+                // So does the settings screen
+                if is_key_pressed(KeyCode::S) {
+                    edited_settings = current;
+                    game_state = GameState::Settings;
                 }
-                if is_key_pressed(KeyCode::Enter) {
-                    difficulty = difficulties[selected_menu];
-                    fall_delay = difficulty.fall_delay();
+
+                // And the profile screen
+                if is_key_pressed(KeyCode::P) {
+                    profile_menu.selected = profiles.current();
+                    game_state = GameState::Profiles;
+                }
+
+                // And the controls screen
+                if is_key_pressed(KeyCode::K) {
+                    edited_bindings = bindings.clone();
+                    capturing = false;
+                    controls_note = None;
+                    game_state = GameState::Controls;
+                }
+
+                // And the high scores, starting at the table of the selected mode
+                if is_key_pressed(KeyCode::H) {
+                    high_score_page = high_scores.table_index(menu.mode, menu.difficulty).unwrap_or(0);
+                    game_state = GameState::HighScores;
+                }
+
+                // Menu navigation logic; puzzle and opener modes pick from their list first
+                if menu.update() {
+                    practice_from_editor = false;
+                    if menu.mode == GameMode::Puzzle {
+                        game_state = GameState::PuzzleSelect;
+                    } else if menu.mode == GameMode::Opener {
+                        game_state = GameState::OpenerSelect;
+                    } else if let GameMode::PerfectClear { pieces } = menu.mode {
+                        pc_drill = PerfectClearDrill::deal(pieces);
+                        engine = Engine::from_perfect_clear(&pc_drill, menu.difficulty);
+                        game_state = GameState::Running;
+                    } else if menu.mode == GameMode::Daily {
+                        // Same seed, pieces and speed for everyone on the same day
+                        daily_date = daily::today();
+                        daily_ranked = daily_history.result(&daily_date).is_none();
+                        if daily_ranked && let Err(e) = daily_history.start(&daily_date) {
+                            eprintln!("Could not save daily attempt: {}", e);
+                        }
+                        engine = Engine::from_daily(daily::seed(&daily_date));
+                        engine.game_info.goal_text = Some(if daily_ranked {
+                            format!("Daily {}", daily_date)
+                        } else {
+                            "Practice".to_owned()
+                        });
+                        game_state = GameState::Running;
+                    } else {
+                        engine = new_game(&menu, &piece_sets, &sprint_records);
+                        game_state = GameState::Running;
+                    }
+                }
+            }
+            GameState::Editor => {
+                editor.draw();
+
+                if editor.update() {
+                    engine = editor.start_practice(menu.difficulty);
+                    practice_from_editor = true;
                     game_state = GameState::Running;
-                    level = 1;
-                    level_timer = 0.0;
-                    game_info.set_level(level); // reset level in game info
+                }
+                if is_key_pressed(KeyCode::Tab) {
+                    game_state = GameState::Waiting;
                 }
             }
-            GameState::Running => {
-                // ---------------------------------------------
-                // Level progression logic
-                // ---------------------------------------------
-                level_timer += get_frame_time();
-                if level_timer > 30.0 {
-                    level_timer = 0.0;
-                    level += 1;
-                    fall_delay = (fall_delay * 0.9).max(0.1);
-                    game_info.set_level(level); // update level in game info
+            GameState::PuzzleSelect => {
+                puzzle_menu.draw_puzzles(&puzzles, &puzzle_progress);
+
+                if puzzle_menu.update(puzzles.len()) {
+                    engine = Engine::from_puzzle(&puzzles[puzzle_menu.selected], menu.difficulty);
+                    game_state = GameState::Running;
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    game_state = GameState::Waiting;
+                }
+            }
+            GameState::OpenerSelect => {
+                opener_menu.draw_openers(&openers);
+
+                if opener_menu.update(openers.len()) {
+                    engine = Engine::from_opener(&openers[opener_menu.selected], menu.difficulty);
+                    game_state = GameState::Running;
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    game_state = GameState::Waiting;
                 }
+            }
+            GameState::Running if engine.mode.has_rewind() && current.rewind && bindings.down(Action::Rewind) => {
+                // Rewind: while the key is held, step back one recorded frame per frame
+                engine.rewind();
 
+                engine.draw_playfield();
+                engine.draw_active();
+                engine.game_info.draw();
+                draw_text("<< Rewind", 10.0, 30.0, 30.0, SKYBLUE);
+            }
+            GameState::Running => {
                 // ---------------------------------------------
-                // Falling logic
+                // Level progression and falling logic
                 // ---------------------------------------------
-                fall_timer += get_frame_time();
-                if fall_timer > fall_delay {
-                    fall_timer = 0.0;
-                    if !check_collision(&grid, &shape, grid_x, grid_y + 1) {
-                        grid_y += 1;
-                    } else {
-                        stack_tetromino(&mut grid, &shape, grid_x, grid_y, color);
-                        // Line clearing and scoring
-                        let lines_cleared = clear_lines(&mut grid);
-                        if lines_cleared > 0 {
-                            game_info.add_score(lines_cleared.try_into().unwrap());
-                        }
-                        // Spawn new tetromino using TetrominoBag
-                        shape_idx = bag.next();
-                        shape = SHAPES[shape_idx];
-                        color = tetromino_colors[shape_idx];
-                        next_idx = bag.peek();
-                        next_shape = SHAPES[next_idx];
-                        next_color = tetromino_colors[next_idx];
-                        game_info.set_next(next_shape, next_color);
-                        grid_x = 3;
-                        grid_y = 0;
-                        // Game over if new tetromino collides immediately
-                        if check_collision(&grid, &shape, grid_x, grid_y) {
-                            game_state = GameState::GameOver;
-                        }
-                    }
-                }
+                let mut outcome = engine.update(get_frame_time());
 
                 // ---------------------------------------------
                 // KEY UP LOGIC
                 // ---------------------------------------------
-                // Controls: rotate, move
-                // if is_key_pressed(KeyCode::Up) || is_key_pressed(KeyCode::X) {
-                //     let rotated = rotate(&shape);
-                //     if !check_collision(&grid, &rotated, grid_x, grid_y) {
-                //         shape = rotated;
-                //     }
-                // }
-
-                // This modified version allow rotation when near border moving block to allow rotation
-                // TODO: test and decide which one is better
-                //
-                if is_key_pressed(KeyCode::Up) || is_key_pressed(KeyCode::X) {
-                    let rotated = rotate(&shape);
-
-                    // Try rotating in place
-                    if !check_collision(&grid, &rotated, grid_x, grid_y) {
-                        shape = rotated;
-                    } else {
-                        // Try wall kicks: left/right by 1 or 2 cells
-                        let kicks = [-1, 1, -2, 2];
-                        // let mut kicked = false;
-                        for &dx in &kicks {
-                            if !check_collision(&grid, &rotated, grid_x + dx, grid_y) {
-                                shape = rotated;
-                                grid_x += dx;
-                                // kicked = true;
-                                break;
-                            }
-                        }
-                        // If none of the kicks work, do not rotate
-                    }
+                // Rotation allows wall kicks near borders or other blocks
+                if bindings.pressed(Action::Rotate) {
+                    engine.count_input();
+                    engine.try_rotate();
                 }
 
                 // ---------------------------------------------
                 // KEY RIGHT LOGIC
                 // ---------------------------------------------
                 // Soft drop logic for right movement
-                if is_key_down(KeyCode::Right) {
-                    timers.soft_drop_right += get_frame_time();
-                    if timers.soft_drop_right > SOFT_DROP_DELAY_HORIZONTAL {
-                        timers.soft_drop_right = 0.0;
-                        if !check_collision(&grid, &shape, grid_x + 1, grid_y) {
-                            grid_x += 1;
-                        }
+                if bindings.down(Action::MoveRight) {
+                    engine.timers.soft_drop_right += get_frame_time();
+                    if engine.timers.soft_drop_right > current.move_repeat {
+                        engine.timers.soft_drop_right = 0.0;
+                        engine.try_move(1, 0);
                     }
                 } else {
-                    timers.soft_drop_right = 0.0;
+                    engine.timers.soft_drop_right = 0.0;
                 }
                 // Also allow instant move right on key press
-                if is_key_pressed(KeyCode::Right) {
-                    if !check_collision(&grid, &shape, grid_x + 1, grid_y) {
-                        grid_x += 1;
-                    }
+                if bindings.pressed(Action::MoveRight) {
+                    engine.count_input();
+                    engine.try_move(1, 0);
                 }
 
                 // ---------------------------------------------
                 // KEY LEFT LOGIC
                 // ---------------------------------------------
                 // Soft drop logic for left movement
-                if is_key_down(KeyCode::Left) {
-                    timers.soft_drop_left += get_frame_time();
-                    if timers.soft_drop_left > SOFT_DROP_DELAY_HORIZONTAL {
-                        timers.soft_drop_left = 0.0;
-                        if !check_collision(&grid, &shape, grid_x - 1, grid_y) {
-                            grid_x -= 1;
-                        }
+                if bindings.down(Action::MoveLeft) {
+                    engine.timers.soft_drop_left += get_frame_time();
+                    if engine.timers.soft_drop_left > current.move_repeat {
+                        engine.timers.soft_drop_left = 0.0;
+                        engine.try_move(-1, 0);
                     }
                 } else {
-                    timers.soft_drop_left = 0.0;
+                    engine.timers.soft_drop_left = 0.0;
                 }
                 // Also allow instant move left on key press
-                if is_key_pressed(KeyCode::Left) {
-                    if !check_collision(&grid, &shape, grid_x - 1, grid_y) {
-                        grid_x -= 1;
-                    }
+                if bindings.pressed(Action::MoveLeft) {
+                    engine.count_input();
+                    engine.try_move(-1, 0);
                 }
 
                 // ---------------------------------------------
                 // KEY DOWN LOGIC
                 // ---------------------------------------------
                 // Soft drop logic for down movement
-                if is_key_down(KeyCode::Down) {
-                    timers.soft_drop_down += get_frame_time();
-                    if timers.soft_drop_down > SOFT_DROP_DELAY_VERTICAL {
-                        timers.soft_drop_down = 0.0;
-                        if !check_collision(&grid, &shape, grid_x, grid_y + 1) {
-                            grid_y += 1;
+                if bindings.down(Action::SoftDrop) {
+                    engine.timers.soft_drop_down += get_frame_time();
+                    if engine.timers.soft_drop_down > current.soft_drop_repeat {
+                        engine.timers.soft_drop_down = 0.0;
+                        if outcome.is_none() {
+                            outcome = engine.soft_drop();
                         }
                     }
                 } else {
-                    timers.soft_drop_down = 0.0;
+                    engine.timers.soft_drop_down = 0.0;
                 }
                 // Also allow instant drop on key press for responsiveness
-                if is_key_pressed(KeyCode::Down) {
-                    if !check_collision(&grid, &shape, grid_x, grid_y + 1) {
-                        grid_y += 1;
-                    }
+                if bindings.pressed(Action::SoftDrop) && outcome.is_none() {
+                    outcome = engine.soft_drop();
                 }
 
                 // ---------------------------------------------
-                // KEY SPACE LOGIC
+                // HARD DROP LOGIC
                 // ---------------------------------------------
-                if is_key_pressed(KeyCode::Space) {
-                    // Hard drop
-                    while !check_collision(&grid, &shape, grid_x, grid_y + 1) {
-                        grid_y += 1;
+                if bindings.pressed(Action::HardDrop) && outcome.is_none() {
+                    outcome = engine.hard_drop();
+                }
+
+                // ---------------------------------------------
+                // KEY HOLD LOGIC
+                // ---------------------------------------------
+                if bindings.pressed(Action::Hold) && outcome.is_none() {
+                    outcome = engine.hold();
+                }
+
+                // Practice tools: pick the next piece, undo, reset and gravity switch
+                if engine.mode == GameMode::Practice && outcome.is_none() {
+                    for (idx, key) in PIECE_KEYS.iter().enumerate() {
+                        if is_key_pressed(*key) {
+                            engine.choose_next(idx);
+                        }
+                    }
+                    if is_key_pressed(KeyCode::Backspace) {
+                        engine.undo();
+                    }
+                    if is_key_pressed(KeyCode::G) {
+                        engine.toggle_gravity();
+                    }
+                    if bindings.pressed(Action::Retry) {
+                        engine = if practice_from_editor {
+                            editor.start_practice(menu.difficulty)
+                        } else {
+                            Engine::new(
+                                GameMode::Practice,
+                                menu.difficulty,
+                                piece_sets[menu.piece_set].clone(),
+                            )
+                        };
+                    }
+                    if practice_from_editor && is_key_pressed(KeyCode::Tab) {
+                        editor.open();
+                        game_state = GameState::Editor;
                     }
                 }
 
+                // Puzzles can be retried at any time
+                if engine.mode == GameMode::Puzzle && bindings.pressed(Action::Retry) {
+                    engine = Engine::from_puzzle(&puzzles[puzzle_menu.selected], menu.difficulty);
+                    outcome = None;
+                }
+
+                // Openers too
+                if engine.mode == GameMode::Opener && bindings.pressed(Action::Retry) {
+                    engine = Engine::from_opener(&openers[opener_menu.selected], menu.difficulty);
+                    outcome = None;
+                }
+
+                // Perfect clear drills retry the same pieces
+                if matches!(engine.mode, GameMode::PerfectClear { .. }) && bindings.pressed(Action::Retry) {
+                    engine = Engine::from_perfect_clear(&pc_drill, menu.difficulty);
+                    outcome = None;
+                }
+
                 // Pause logic moved here
-                if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::P) {
+                if bindings.pressed(Action::Pause) {
                     game_state = GameState::Paused;
                 }
 
-                // Draw grid lines
-                draw_grid(GRAY);
-                // Draw stacked blocks
-                draw_grid_blocks(&grid);
+                // Sprints with finesse restart start over at the first fault
+                if outcome == Some(Outcome::Failed) && engine.mode.restarts_on_fault() {
+                    engine = new_game(&menu, &piece_sets, &sprint_records);
+                    outcome = None;
+                }
+
+                // Game end: build the results and record progress
+                if let Some(outcome) = outcome {
+                    results = finish_game(&engine, outcome, &mut sprint_records);
+                    if let Err(e) = statistics.record(&engine.game_info) {
+                        results.push(format!("Could not save statistics: {}", e), RED);
+                    }
+                    if engine.mode == GameMode::Puzzle
+                        && outcome == Outcome::Completed
+                        && let Err(e) = puzzle_progress.mark_solved(&puzzles[puzzle_menu.selected].id)
+                    {
+                        results.push(format!("Could not save progress: {}", e), RED);
+                    }
+                    if matches!(engine.mode, GameMode::PerfectClear { .. }) {
+                        if pc_drill.solution.is_some() {
+                            results.push("A hard drop solution exists:".to_owned(), LIGHTGRAY);
+                        } else {
+                            results.push("No hard drop solution found".to_owned(), LIGHTGRAY);
+                        }
+                    }
+                    if engine.mode == GameMode::Daily {
+                        record_daily(
+                            &engine,
+                            &mut results,
+                            &mut daily_history,
+                            &daily_date,
+                            daily_ranked,
+                        );
+                    }
+                    game_state = GameState::GameOver(outcome);
+
+                    // A new high score asks for the player's name first
+                    if let Some(rank) =
+                        high_scores.rank(engine.mode, menu.difficulty, outcome, &engine.game_info)
+                    {
+                        new_rank = rank;
+                        name = high_scores.last_name.clone();
+                        if name.is_empty() && profiles.last != DEFAULT_PROFILE {
+                            name = profiles.last.clone();
+                        }
+                        clear_input_queue();
+                        game_state = GameState::NameEntry(outcome);
+                    }
+                }
+
+                // Draw grid lines and stacked blocks
+                engine.draw_playfield();
                 // Draw active tetromino
-                draw_tetromino(&shape, grid_x, grid_y, color);
+                engine.draw_active();
                 // Draw game info panel (score, lines, next)
-                game_info.draw();
+                engine.game_info.draw();
             }
             GameState::Paused => {
                 // Draw the grid and stacked blocks as usual
-                draw_grid(GRAY);
-                draw_grid_blocks(&grid);
+                engine.draw_playfield();
 
                 // Draw a semi-transparent overlay to "blur" or dim the grid
                 draw_rectangle(
                     0.0,
                     0.0,
-                    GRID_WIDTH as f32 * BLOCK_SIZE,
-                    GRID_HEIGHT as f32 * BLOCK_SIZE,
+                    GRID_WIDTH as f32 * block_size(),
+                    GRID_HEIGHT as f32 * block_size(),
                     Color::new(0.2, 0.2, 0.2, 0.7),
                 );
 
                 // Draw the info panel as usual
-                game_info.draw();
+                engine.game_info.draw();
 
                 // Draw "Paused" text in the center
                 draw_centered_text("Paused", 60.0, YELLOW);
                 
                 // Unpause logic moved here
-                if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::P) {
+                if bindings.pressed(Action::Pause) {
                     game_state = GameState::Running;
                 }
             }
-            GameState::GameOver => {
+            GameState::NameEntry(outcome) => {
+                engine.draw_playfield();
+                draw_rectangle(
+                    0.0,
+                    0.0,
+                    GRID_WIDTH as f32 * block_size(),
+                    GRID_HEIGHT as f32 * block_size(),
+                    Color::new(0.2, 0.2, 0.2, 0.7),
+                );
+                engine.game_info.draw();
+                draw_name_entry(new_rank, &name);
+
+                // Characters arrive most recent first
+                let mut typed: Vec<char> = std::iter::from_fn(get_char_pressed).collect();
+                typed.reverse();
+                for c in typed {
+                    if (c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
+                        && name.chars().count() < MAX_NAME_LENGTH
+                    {
+                        name.push(c);
+                    }
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    name.pop();
+                }
+                if is_key_pressed(KeyCode::Enter) {
+                    let player = match name.trim() {
+                        "" => "Player",
+                        trimmed => trimmed,
+                    };
+                    match high_scores.submit(engine.mode, menu.difficulty, &engine.game_info, player) {
+                        Ok(()) => results.push(format!("New high score: #{}", new_rank + 1), GOLD),
+                        Err(e) => results.push(format!("Could not save high score: {}", e), RED),
+                    }
+                    game_state = GameState::GameOver(outcome);
+                }
+            }
+            GameState::HighScores => {
+                match high_scores.tables.get(high_score_page) {
+                    Some(table) => draw_table(
+                        "High Scores",
+                        &format!("< {} >", table.name),
+                        &table.columns(),
+                        &table.rows(),
+                        "Left/Right: table   Backspace: back",
+                    ),
+                    None => draw_table("High Scores", "", &[], &[], "Backspace: back"),
+                }
+
+                let count = high_scores.tables.len().max(1);
+                if is_key_pressed(KeyCode::Left) {
+                    high_score_page = (high_score_page + count - 1) % count;
+                }
+                if is_key_pressed(KeyCode::Right) {
+                    high_score_page = (high_score_page + 1) % count;
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    game_state = GameState::Waiting;
+                }
+            }
+            GameState::Settings => {
+                settings_menu.draw_settings(&edited_settings);
+                if let Some(error) = settings_errors.first() {
+                    draw_menu_note(error, RED);
+                }
+
+                if is_key_pressed(KeyCode::Left) {
+                    edited_settings.change_option(settings_menu.selected, -1);
+                }
+                if is_key_pressed(KeyCode::Right) {
+                    edited_settings.change_option(settings_menu.selected, 1);
+                }
+                // Enter saves the settings and puts them in effect
+                if settings_menu.update(edited_settings.options().len()) {
+                    match edited_settings.save() {
+                        Ok(()) => {
+                            settings::apply(edited_settings);
+                            settings_errors.clear();
+                            game_state = GameState::Waiting;
+                        }
+                        Err(e) => settings_errors = vec![format!("Could not save settings: {}", e)],
+                    }
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    game_state = GameState::Waiting;
+                }
+            }
+            GameState::Controls => {
+                controls_menu.draw_controls(&edited_bindings, capturing);
+                match &controls_note {
+                    Some((note, color)) => draw_menu_note(note, *color),
+                    None if !capturing => draw_menu_note("Backspace: discard changes", GRAY),
+                    None => {}
+                }
+
+                if capturing {
+                    // The next key pressed is added to the selected action; Escape cancels
+                    let action = Action::ALL[controls_menu.selected];
+                    if is_key_pressed(KeyCode::Escape) {
+                        capturing = false;
+                        controls_note = None;
+                    } else if let Some(key) = get_last_key_pressed() {
+                        controls_note = edited_bindings.bind(action, key).err().map(|e| (e, RED));
+                        capturing = false;
+                    }
+                } else {
+                    if controls_menu.update(Action::ALL.len()) {
+                        capturing = true;
+                        controls_note = None;
+                    }
+                    if is_key_pressed(KeyCode::Delete) {
+                        edited_bindings.clear(Action::ALL[controls_menu.selected]);
+                    }
+                    // Tab loads the next preset
+                    if is_key_pressed(KeyCode::Tab) {
+                        let idx = Preset::ALL
+                            .iter()
+                            .position(|p| edited_bindings == Bindings::preset(*p))
+                            .map_or(0, |idx| (idx + 1) % Preset::ALL.len());
+                        edited_bindings = Bindings::preset(Preset::ALL[idx]);
+                        controls_note = Some((format!("Preset: {}", Preset::ALL[idx].name()), SKYBLUE));
+                    }
+                    // S saves the bindings, once every action has its own keys
+                    if is_key_pressed(KeyCode::S) {
+                        match edited_bindings.problem() {
+                            Some(problem) => controls_note = Some((problem, RED)),
+                            None => match edited_bindings.save() {
+                                Ok(()) => {
+                                    bindings = edited_bindings.clone();
+                                    binding_errors.clear();
+                                    game_state = GameState::Waiting;
+                                }
+                                Err(e) => controls_note = Some((format!("Could not save: {}", e), RED)),
+                            },
+                        }
+                    }
+                    // Backspace leaves without saving, discarding the changes
+                    if is_key_pressed(KeyCode::Backspace) {
+                        edited_bindings = bindings.clone();
+                        game_state = GameState::Waiting;
+                    }
+                }
+            }
+            GameState::GameOver(_) => {
                 // Draw the grid and stacked blocks as usual
-                draw_grid(GRAY);
-                draw_grid_blocks(&grid);
+                engine.draw_playfield();
 
                 // Draw a semi-transparent overlay to "blur" or dim the grid
                 draw_rectangle(
                     0.0,
                     0.0,
-                    GRID_WIDTH as f32 * BLOCK_SIZE,
-                    GRID_HEIGHT as f32 * BLOCK_SIZE,
+                    GRID_WIDTH as f32 * block_size(),
+                    GRID_HEIGHT as f32 * block_size(),
                     Color::new(0.2, 0.2, 0.2, 0.7),
                 );
 
                 // Draw the info panel as usual
-                game_info.draw();
+                engine.game_info.draw();
 
-                // Draw "Game Over" text in the center
-                draw_centered_text("Game Over", 60.0, RED);
-                draw_bottom_centered_text("Press Enter to play again", 36.0, YELLOW);
+                // Draw "Game Over" (or the completed goal) text in the center, with the results below
+                draw_results(&results);
+                if matches!(engine.mode, GameMode::PerfectClear { .. }) {
+                    // One solution found by the solver, above the results
+                    pc_drill.draw_solution(60.0, 80.0, 18.0);
+                    draw_bottom_centered_text("Enter: menu   R: retry   N: new", 36.0, YELLOW);
+                } else if engine.mode == GameMode::Puzzle {
+                    draw_bottom_centered_text("Enter: puzzles   R: retry", 36.0, YELLOW);
+                } else if engine.mode == GameMode::Opener {
+                    draw_bottom_centered_text("Enter: openers   R: retry", 36.0, YELLOW);
+                } else if engine.mode == GameMode::Practice {
+                    let back = if practice_from_editor { "editor" } else { "menu" };
+                    draw_bottom_centered_text(
+                        &format!("Enter: {}   Backspace: undo", back),
+                        36.0,
+                        YELLOW,
+                    );
+                } else {
+                    draw_bottom_centered_text("Press Enter to play again", 36.0, YELLOW);
+                }
 
-                // Restart logic
+                // Restart logic: go back to the start menu (or the puzzle list),
+                // a fresh engine is created when the next game starts
                 if is_key_pressed(KeyCode::Enter) {
-                    // Reset all game state as at the start
-                    grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
-                    bag = TetrominoBag::new();
-                    shape_idx = bag.next();
-                    shape = SHAPES[shape_idx];
-                    color = tetromino_colors[shape_idx];
-                    next_idx = bag.peek();
-                    next_shape = SHAPES[next_idx];
-                    next_color = tetromino_colors[next_idx];
-                    game_info = GameInfo::new(next_shape, next_color);
-                    grid_x = 3;
-                    grid_y = 0;
-                    fall_timer = 0.0;
-                    timers = Timers::default();
-                    level = 1;
-                    level_timer = 0.0;
-                    game_info.set_level(level);
-
-                    // Optionally, go back to difficulty selection:
-                    game_state = GameState::Waiting;
-                    // Or, to start immediately:
-                    // game_state = GameState::Running;
+                    game_state = if engine.mode == GameMode::Puzzle {
+                        GameState::PuzzleSelect
+                    } else if engine.mode == GameMode::Opener {
+                        GameState::OpenerSelect
+                    } else if practice_from_editor {
+                        editor.open();
+                        GameState::Editor
+                    } else {
+                        GameState::Waiting
+                    };
+                } else if engine.mode == GameMode::Practice
+                    && is_key_pressed(KeyCode::Backspace)
+                    && engine.undo()
+                {
+                    resume_game(&mut statistics);
+                    game_state = GameState::Running;
+                } else if engine.mode == GameMode::Puzzle && bindings.pressed(Action::Retry) {
+                    engine = Engine::from_puzzle(&puzzles[puzzle_menu.selected], menu.difficulty);
+                    game_state = GameState::Running;
+                } else if engine.mode == GameMode::Opener && bindings.pressed(Action::Retry) {
+                    engine = Engine::from_opener(&openers[opener_menu.selected], menu.difficulty);
+                    game_state = GameState::Running;
+                } else if let GameMode::PerfectClear { pieces } = engine.mode {
+                    if is_key_pressed(KeyCode::N) {
+                        pc_drill = PerfectClearDrill::deal(pieces);
+                    }
+                    if bindings.pressed(Action::Retry) || is_key_pressed(KeyCode::N) {
+                        engine = Engine::from_perfect_clear(&pc_drill, menu.difficulty);
+                        game_state = GameState::Running;
+                    }
+                } else if engine.mode.has_rewind() && current.rewind && bindings.down(Action::Rewind) {
+                    // Rewinding takes the game back from its end
+                    resume_game(&mut statistics);
+                    game_state = GameState::Running;
                 }
             }
         }
 
-        if is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::Q) {
-            break;
+        // Quitting takes a second press of a Quit key
+        quit_prompt = (quit_prompt - get_frame_time()).max(0.0);
+        if bindings.pressed(Action::Quit) && !typing {
+            if quit_prompt > 0.0 {
+                break;
+            }
+            quit_prompt = QUIT_CONFIRM_TIME;
+        }
+        if quit_prompt > 0.0 {
+            draw_banner(&format!("Press {} again to quit", bindings.describe(Action::Quit)));
         }
         next_frame().await
     }
 }
+
+/// Takes a game that ended back out of the statistics as it resumes; it is recorded again when it
+/// ends for good.
+fn resume_game(statistics: &mut Statistics) {
+    if let Err(e) = statistics.take_back_last() {
+        eprintln!("Could not save statistics: {}", e);
+    }
+}
+
+/// Creates a new game with the options selected on the start menu.
+///
+/// # Arguments
+/// * `menu` - The start menu with the selected mode, difficulty and piece set
+/// * `piece_sets` - The loaded piece sets
+/// * `sprint_records` - The sprint personal bests, whose splits are shown during a sprint
+fn new_game(menu: &StartMenu, piece_sets: &[PieceSet], sprint_records: &SprintRecords) -> Engine {
+    let mut engine = Engine::new(menu.mode, menu.difficulty, piece_sets[menu.piece_set].clone());
+    if let GameMode::Sprint { lines, .. } = menu.mode {
+        engine.game_info.best_splits = sprint_records
+            .best(lines)
+            .map(|best| best.splits.clone())
+            .unwrap_or_default();
+    }
+    engine
+}
+
+/// Builds the results screen of a finished game and updates the personal records.
+///
+/// # Arguments
+/// * `engine` - The engine of the game that just ended
+/// * `outcome` - How the game ended
+/// * `sprint_records` - The sprint personal bests, updated and saved on a completed sprint
+/// # Returns
+/// * The results to show on the game over screen.
+fn finish_game(engine: &Engine, outcome: Outcome, sprint_records: &mut SprintRecords) -> Results {
+    let info = &engine.game_info;
+
+    let mut results = match (engine.mode, outcome) {
+        (GameMode::Puzzle, Outcome::Completed) => Results::new("Solved!", GOLD),
+        (GameMode::Puzzle, _) => {
+            let mut results = Results::new("Failed", RED);
+            results.push("Press R to retry".to_owned(), GRAY);
+            results
+        }
+        (GameMode::Opener, Outcome::Completed) => {
+            let mut results = Results::new("Opener done!", GOLD);
+            results.push(format!("Time: {}", format_time(info.time)), SKYBLUE);
+            results
+        }
+        (GameMode::Opener, _) => {
+            let mut results = Results::new("Missed", RED);
+            // The panel still shows the step that was missed
+            if let Some(step) = &info.goal_text {
+                results.push(step.clone(), GRAY);
+            }
+            results.push("Press R to retry".to_owned(), GRAY);
+            results
+        }
+        (GameMode::PerfectClear { .. }, Outcome::Completed) => {
+            let mut results = Results::new("Perfect clear!", GOLD);
+            results.push(format!("Time: {}", format_time(info.time)), SKYBLUE);
+            results
+        }
+        (GameMode::PerfectClear { .. }, _) => {
+            let mut results = Results::new("No perfect clear", RED);
+            results.push(format!("Lines: {}", info.lines_cleared), WHITE);
+            results
+        }
+        (GameMode::Dig { .. }, Outcome::ToppedOut) => {
+            let mut results = Results::new("Game Over", RED);
+            results.push(format!("Garbage dug: {}", info.garbage_dug), LIGHTGRAY);
+            results.push(format!("Time: {}", format_time(info.time)), SKYBLUE);
+            results
+        }
+        (GameMode::Master, _) => {
+            let mut results = if outcome == Outcome::Completed {
+                Results::new("Level 999!", GOLD)
+            } else {
+                Results::new("Game Over", RED)
+            };
+            results.push(format!("Grade: {}", info.grade.unwrap_or("9")), GOLD);
+            results.push(format!("Score: {}", info.score), YELLOW);
+            results.push(format!("Level: {}", info.level), WHITE);
+            results.push(format!("Time: {}", format_time(info.time)), SKYBLUE);
+            for (i, section) in info.section_times.iter().enumerate() {
+                results.push(
+                    format!("Section {}: {}", i + 1, format_time(*section)),
+                    SKYBLUE,
+                );
+            }
+            results
+        }
+        (GameMode::Classic { .. }, Outcome::ToppedOut) => {
+            let mut results = Results::new("Game Over", RED);
+            results.push(format!("Score: {}", info.score), YELLOW);
+            results.push(format!("Lines: {}", info.lines_cleared), WHITE);
+            results.push(format!("Level: {}", info.level), WHITE);
+            results
+        }
+        (GameMode::Survival { .. }, Outcome::ToppedOut) => {
+            let mut results = Results::new("Game Over", RED);
+            results.push(format!("Survived: {}", format_time(info.time)), SKYBLUE);
+            results.push(format!("Lines: {}", info.lines_cleared), WHITE);
+            results.push(format!("Garbage risen: {}", info.garbage_risen), LIGHTGRAY);
+            results
+        }
+        (GameMode::Combo, Outcome::ToppedOut) => {
+            let mut results = Results::new("Game Over", RED);
+            results.push(format!("Max combo: {}", info.max_combo), ORANGE);
+            results.push(format!("Combos: {}", info.combos), ORANGE);
+            results.push(format!("Lines: {}", info.lines_cleared), WHITE);
+            results
+        }
+        (_, Outcome::ToppedOut) => Results::new("Game Over", RED),
+        (GameMode::Sprint { lines, .. }, Outcome::Completed) => {
+            let mut results = Results::new("Finished!", GREEN);
+            let previous = sprint_records.best(lines).map(|best| best.time);
+            results.push(format!("Time: {}", format_time(info.time)), WHITE);
+            if sprint_records.submit(lines, info.time, &info.splits) {
+                results.push("New personal best!".to_owned(), GREEN);
+                if let Err(e) = sprint_records.save() {
+                    results.push(format!("Could not save record: {}", e), RED);
+                }
+            } else if let Some(best) = previous {
+                results.push(format!("Best: {}", format_time(best)), GRAY);
+            }
+            // Split times compared against the previous personal best
+            for (i, split) in info.splits.iter().enumerate() {
+                let (delta, color) = match info.split_delta(i) {
+                    Some(d) if d <= 0.0 => (format!(" -{:.2}", -d), GREEN),
+                    Some(d) => (format!(" +{:.2}", d), RED),
+                    None => (String::new(), WHITE),
+                };
+                results.push(
+                    format!("{}: {}{}", (i as u32 + 1) * SPLIT_LINES, format_time(*split), delta),
+                    color,
+                );
+            }
+            results
+        }
+        (GameMode::Ultra { .. } | GameMode::Daily, Outcome::Completed) => {
+            let mut results = Results::new("Time's up!", GREEN);
+            results.push(format!("Score: {}", info.score), YELLOW);
+            results.push(format!("Lines: {}", info.lines_cleared), WHITE);
+            results
+        }
+        (GameMode::Marathon { .. }, Outcome::Completed) => {
+            let mut results = Results::new("Cleared!", GOLD);
+            results.push(format!("Score: {}", info.score), YELLOW);
+            results.push(format!("Lines: {}", info.lines_cleared), WHITE);
+            results.push(format!("Level: {}", info.level), WHITE);
+            results.push(format!("Time: {}", format_time(info.time)), SKYBLUE);
+            results
+        }
+        (GameMode::Dig { .. }, Outcome::Completed) => {
+            let mut results = Results::new("Dug out!", GREEN);
+            results.push(format!("Time: {}", format_time(info.time)), SKYBLUE);
+            results.push(format!("Lines: {}", info.lines_cleared), WHITE);
+            results
+        }
+        (_, Outcome::Completed) => Results::new("Finished!", GREEN),
+        (_, Outcome::Failed) => Results::new("Game Over", RED),
+    };
+
+    if let Some(percent) = info.finesse_percent() {
+        results.push(
+            format!("Finesse: {:.1}% ({} faults)", percent, info.finesse_faults),
+            LIGHTGRAY,
+        );
+    }
+    results
+}
+
+/// Records a finished daily challenge in the history if it was the day's ranked game,
+/// and adds the ranking and the recent daily results to the results screen.
+///
+/// # Arguments
+/// * `engine` - The engine of the daily game that just ended
+/// * `results` - The results screen, extended with the daily information
+/// * `history` - The daily history, updated and saved on a ranked game
+/// * `date` - The date of the daily game
+/// * `ranked` - Whether the game was the day's scored attempt
+fn record_daily(
+    engine: &Engine,
+    results: &mut Results,
+    history: &mut DailyHistory,
+    date: &str,
+    ranked: bool,
+) {
+    if ranked {
+        let result = DailyResult {
+            date: date.to_owned(),
+            score: engine.game_info.score,
+            lines: engine.game_info.lines_cleared,
+            finished: true,
+        };
+        if let Err(e) = history.record(result) {
+            results.push(format!("Could not save daily result: {}", e), RED);
+        }
+    } else {
+        results.push("Practice (not ranked)".to_owned(), GRAY);
+    }
+    for result in history.results.iter().rev().take(5) {
+        if result.finished {
+            results.push(format!("{}: {}", result.date, result.score), LIGHTGRAY);
+        } else {
+            results.push(format!("{}: abandoned", result.date), LIGHTGRAY);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// This module defines the rules of Master mode, modelled on the arcade "Tetris The Grand Master" games:
+// a level counter that drives gravity and timings, section stops, TGM scoring and grades.
+//
+// Principles and design choices:
+//
+// - The level rises by one for every piece that spawns and by the number of lines cleared, except that
+//   spawning a piece never passes a section stop (x99, and 998 before the final 999).
+// - Gravity comes from a table in 1/256 cells per frame, reaching 20G (the piece drops to the bottom at
+//   once) from level 500; lock delay, entry delay (ARE) and line clear delay shorten in the last sections.
+// - All tables are written in frames at 60 frames per second, like the arcade originals, and converted
+//   to seconds so the engine keeps working with frame times from macroquad.
+// - The MasterState holds the counters the engine needs while playing (lock timer, gravity progress,
+//   entry delay, combo) and the section times shown in the panel and the results.
+// - Grades are computed from the score, with Grand Master requiring level 999 within the time limit.
+
+/// Frames per second the timing tables are written for.
+pub const FRAME_RATE: f32 = 60.0;
+
+/// Level that completes the game.
+pub const MAX_LEVEL: usize = 999;
+
+/// Levels in each section.
+pub const SECTION_LEVELS: usize = 100;
+
+/// Gravity that drops the piece to the bottom at once, in 1/256 cells per frame.
+const GRAVITY_20G: u32 = 5120;
+
+/// Gravity (1/256 cells per frame) from each level on.
+const GRAVITY_TABLE: [(usize, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, GRAVITY_20G),
+];
+
+/// Entry delay, lock delay and line clear delay (in frames) from each level on.
+const TIMING_TABLE: [(usize, u32, u32, u32); 6] = [
+    (0, 25, 30, 40),
+    (500, 25, 30, 25),
+    (600, 25, 30, 16),
+    (700, 16, 30, 12),
+    (800, 12, 30, 6),
+    (900, 12, 17, 6),
+];
+
+/// Minimum score for each grade, from the lowest (9) to the highest (S9).
+const GRADE_TABLE: [(u32, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1400, "6"),
+    (2000, "5"),
+    (3500, "4"),
+    (5500, "3"),
+    (8000, "2"),
+    (12000, "1"),
+    (16000, "S1"),
+    (22000, "S2"),
+    (30000, "S3"),
+    (40000, "S4"),
+    (52000, "S5"),
+    (66000, "S6"),
+    (82000, "S7"),
+    (100000, "S8"),
+    (120000, "S9"),
+];
+
+/// Score needed at level 999 for the Grand Master grade.
+const GM_SCORE: u32 = 126000;
+
+/// Time (in seconds) within which level 999 must be reached for the Grand Master grade.
+const GM_TIME: f64 = 13.0 * 60.0 + 30.0;
+
+/// Delays of the current section, in seconds.
+pub struct Timings {
+    pub entry: f32,
+    pub lock: f32,
+    pub line_clear: f32,
+}
+
+/// State of a Master game in progress.
+pub struct MasterState {
+    pub level: usize,
+    /// Seconds the active piece has been resting on the stack.
+    pub lock_timer: f32,
+    /// Fraction of a cell the active piece has fallen since its last step down.
+    pub gravity_progress: f32,
+    /// Seconds left before the next piece spawns, while waiting between pieces.
+    pub entry_delay: Option<f32>,
+    /// Rows the active piece has been soft dropped, which add to the score.
+    pub soft_rows: u32,
+    combo: u32,
+    /// Time taken by each finished section.
+    pub section_times: Vec<f64>,
+    section_start: f64,
+}
+
+/// Implementation of MasterState methods.
+impl MasterState {
+    /// Creates the state of a new game at level 0.
+    pub fn new() -> Self {
+        Self {
+            level: 0,
+            lock_timer: 0.0,
+            gravity_progress: 0.0,
+            entry_delay: None,
+            soft_rows: 0,
+            combo: 1,
+            section_times: Vec::new(),
+            section_start: 0.0,
+        }
+    }
+
+    /// Returns the gravity at the current level, in cells per frame.
+    pub fn gravity(&self) -> f32 {
+        let (_, gravity) = GRAVITY_TABLE
+            .iter()
+            .rev()
+            .find(|(level, _)| self.level >= *level)
+            .unwrap_or(&GRAVITY_TABLE[0]);
+        *gravity as f32 / 256.0
+    }
+
+    /// Returns the delays at the current level.
+    pub fn timings(&self) -> Timings {
+        let (_, entry, lock, line_clear) = TIMING_TABLE
+            .iter()
+            .rev()
+            .find(|(level, ..)| self.level >= *level)
+            .unwrap_or(&TIMING_TABLE[0]);
+        Timings {
+            entry: *entry as f32 / FRAME_RATE,
+            lock: *lock as f32 / FRAME_RATE,
+            line_clear: *line_clear as f32 / FRAME_RATE,
+        }
+    }
+
+    /// Returns the level that ends the current section (x99 stop, or 999 in the last section).
+    pub fn section_target(&self) -> usize {
+        ((self.level / SECTION_LEVELS + 1) * SECTION_LEVELS).min(MAX_LEVEL)
+    }
+
+    /// Advances the level for a newly spawned piece, unless it sits at a section stop.
+    pub fn on_spawn(&mut self) {
+        let stop = self.section_target() - 1;
+        if self.level < stop {
+            self.level += 1;
+        }
+        self.lock_timer = 0.0;
+        self.gravity_progress = 0.0;
+        self.soft_rows = 0;
+    }
+
+    /// Scores a locked piece, advances the level by the lines cleared and records section times.
+    ///
+    /// # Arguments
+    /// * `lines` - The number of lines the piece cleared
+    /// * `bravo` - Whether the clear left the board empty
+    /// * `time` - The current play time in seconds
+    /// # Returns
+    /// * The points earned by the piece.
+    pub fn on_lock(&mut self, lines: u32, bravo: bool, time: f64) -> u32 {
+        if lines == 0 {
+            self.combo = 1;
+            return 0;
+        }
+        self.combo += 2 * lines - 2;
+        let bravo = if bravo { 4 } else { 1 };
+        let points = ((self.level as u32 + lines).div_ceil(4) + self.soft_rows) * lines * self.combo * bravo;
+
+        let section = self.level / SECTION_LEVELS;
+        self.level = (self.level + lines as usize).min(MAX_LEVEL);
+        let finished_sections = if self.level == MAX_LEVEL {
+            MAX_LEVEL / SECTION_LEVELS + 1
+        } else {
+            self.level / SECTION_LEVELS
+        };
+        for _ in section..finished_sections {
+            self.section_times.push(time - self.section_start);
+            self.section_start = time;
+        }
+        points
+    }
+
+    /// Whether the final level has been reached.
+    pub fn finished(&self) -> bool {
+        self.level >= MAX_LEVEL
+    }
+
+    /// Returns the grade earned with the given score, with Grand Master for a fast enough finish.
+    ///
+    /// # Arguments
+    /// * `score` - The current score
+    /// * `time` - The current play time in seconds
+    pub fn grade(&self, score: u32, time: f64) -> &'static str {
+        if self.finished() && score >= GM_SCORE && time <= GM_TIME {
+            return "GM";
+        }
+        GRADE_TABLE
+            .iter()
+            .rev()
+            .find(|(min_score, _)| score >= *min_score)
+            .map(|(_, grade)| *grade)
+            .unwrap_or("9")
+    }
+}
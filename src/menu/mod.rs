@@ -0,0 +1,314 @@
+// This module defines the StartMenu, which collects the options of the next game before it starts.
+//
+// Principles and design choices:
+//
+// - The menu is a list of rows (mode, the mode's own options, difficulty, piece set); Up/Down selects
+//   a row and Left/Right cycles its value, so new options only need a new MenuRow variant.
+// - Mode option rows come from GameMode::options(), so the rows change with the selected mode.
+// - The menu only stores the player's choices; the main loop reads them to build the Engine.
+// - The ListMenu tracks the selection in the puzzle and opener lists and on the profile, settings and
+//   controls screens; it draws puzzles with their solved state, openers with their number of steps,
+//   profiles with the one in use, settings with their value and actions with their keys (conflicts in red).
+// - Drawing is delegated to the ui module to keep layout code in one place.
+
+use macroquad::prelude::*;
+
+use crate::bindings::{Action, Bindings};
+use crate::mode::GameMode;
+use crate::opener::{OPENER_DIR, Opener};
+use crate::parameters::Difficulty;
+use crate::piece_set::PieceSet;
+use crate::profiles::Profiles;
+use crate::puzzle::{PUZZLE_DIR, Puzzle, PuzzleProgress};
+use crate::settings::Settings;
+use crate::ui::{draw_list, draw_start_menu};
+
+/// Navigation hint of the puzzle and opener lists.
+const LIST_HINT: &str = "Up/Down: select   Backspace: back";
+
+/// Enum representing the rows of the start menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuRow {
+    Mode,
+    ModeOption(usize),
+    Difficulty,
+    Pieces,
+}
+
+/// Struct holding the start menu selections.
+pub struct StartMenu {
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+    pub piece_set: usize,
+    piece_set_names: Vec<String>,
+    selected: usize,
+}
+
+/// Implementation of StartMenu methods.
+impl StartMenu {
+    /// Creates the start menu with Endless mode and Medium difficulty selected.
+    ///
+    /// # Arguments
+    /// * `piece_sets` - The piece sets the player can choose from
+    pub fn new(piece_sets: &[PieceSet]) -> Self {
+        Self {
+            mode: GameMode::Endless,
+            difficulty: Difficulty::Medium,
+            piece_set: 0,
+            piece_set_names: piece_sets.iter().map(|set| set.name.clone()).collect(),
+            selected: 0,
+        }
+    }
+
+    /// Handles menu navigation for this frame.
+    ///
+    /// # Returns
+    /// * `true` if the player pressed Enter to start the game, `false` otherwise.
+    pub fn update(&mut self) -> bool {
+        if is_key_pressed(KeyCode::Up) && self.selected > 0 {
+            self.selected -= 1;
+        }
+        if is_key_pressed(KeyCode::Down) && self.selected < self.rows().len() - 1 {
+            self.selected += 1;
+        }
+        if is_key_pressed(KeyCode::Left) {
+            self.change(-1);
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.change(1);
+        }
+
+        // Numeric options also take digits typed on the keyboard, Backspace erasing the last one
+        if let MenuRow::ModeOption(index) = self.rows()[self.selected]
+            && self.mode.has_typed_option(index)
+        {
+            let mut typed: Vec<char> = std::iter::from_fn(get_char_pressed).collect();
+            typed.reverse();
+            for digit in typed.into_iter().filter_map(|c| c.to_digit(10)) {
+                self.mode.type_option(index, Some(digit));
+            }
+            if is_key_pressed(KeyCode::Backspace) {
+                self.mode.type_option(index, None);
+            }
+        }
+        is_key_pressed(KeyCode::Enter)
+    }
+
+    /// Returns a hint about the selected row, if it takes typed input or needs explaining.
+    pub fn note(&self) -> Option<&'static str> {
+        match self.rows()[self.selected] {
+            MenuRow::ModeOption(index) if self.mode.has_typed_option(index) => {
+                Some("Left/Right: presets   Type a number: custom goal")
+            }
+            MenuRow::ModeOption(1) if matches!(self.mode, GameMode::Sprint { .. }) => {
+                Some("Judges hard drops only: tucks and spins never fault")
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the rows currently shown, from top to bottom.
+    fn rows(&self) -> Vec<MenuRow> {
+        let mut rows = vec![MenuRow::Mode];
+        rows.extend((0..self.mode.options().len()).map(MenuRow::ModeOption));
+        rows.push(MenuRow::Difficulty);
+        if !self.mode.has_fixed_pieces() {
+            rows.push(MenuRow::Pieces);
+        }
+        rows
+    }
+
+    /// Cycles the value of the selected row forwards (1) or backwards (-1).
+    fn change(&mut self, step: i32) {
+        match self.rows()[self.selected] {
+            MenuRow::Mode => {
+                self.mode = if step > 0 { self.mode.next() } else { self.mode.prev() };
+            }
+            MenuRow::ModeOption(index) => self.mode.change_option(index, step),
+            MenuRow::Difficulty => {
+                let idx = Difficulty::ALL.iter().position(|d| *d == self.difficulty).unwrap_or(0);
+                self.difficulty = Difficulty::ALL[cycle(idx, step, Difficulty::ALL.len())];
+            }
+            MenuRow::Pieces => {
+                self.piece_set = cycle(self.piece_set, step, self.piece_set_names.len());
+            }
+        }
+    }
+
+    /// Draws the start menu.
+    ///
+    /// # Arguments
+    /// * `profile` - The name of the player's profile, shown under the title
+    pub fn draw(&self, profile: &str) {
+        let options = self.mode.options();
+        let rows: Vec<(&str, String)> = self
+            .rows()
+            .iter()
+            .map(|row| match row {
+                MenuRow::Mode => ("Mode", self.mode.name().to_owned()),
+                MenuRow::ModeOption(index) => options[*index].clone(),
+                MenuRow::Difficulty => ("Difficulty", self.difficulty.name().to_owned()),
+                MenuRow::Pieces => ("Pieces", self.piece_set_names[self.piece_set].clone()),
+            })
+            .collect();
+        draw_start_menu("Tetris RS", &format!("Profile: {}  (P: switch)", profile), &rows, self.selected);
+    }
+}
+
+/// Struct holding the selection of a list screen (puzzles or openers).
+pub struct ListMenu {
+    pub selected: usize,
+}
+
+/// Implementation of ListMenu methods.
+impl ListMenu {
+    /// Creates the list menu with the first entry selected.
+    pub fn new() -> Self {
+        Self { selected: 0 }
+    }
+
+    /// Handles list navigation for this frame.
+    ///
+    /// # Arguments
+    /// * `count` - The number of entries in the list
+    /// # Returns
+    /// * `true` if the player pressed Enter on an entry, `false` otherwise.
+    pub fn update(&mut self, count: usize) -> bool {
+        if count == 0 {
+            return false;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = cycle(self.selected, -1, count);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = cycle(self.selected, 1, count);
+        }
+        self.selected = self.selected.min(count - 1);
+        is_key_pressed(KeyCode::Enter)
+    }
+
+    /// Draws the puzzle list, marking solved puzzles.
+    ///
+    /// # Arguments
+    /// * `puzzles` - The loaded puzzles
+    /// * `progress` - The player's solved puzzles
+    pub fn draw_puzzles(&self, puzzles: &[Puzzle], progress: &PuzzleProgress) {
+        let items: Vec<(String, Color)> = puzzles
+            .iter()
+            .map(|puzzle| {
+                if progress.is_solved(&puzzle.id) {
+                    (format!("{} (solved)", puzzle.id), GREEN)
+                } else {
+                    (puzzle.id.clone(), WHITE)
+                }
+            })
+            .collect();
+        draw_list("Puzzles", &items, self.selected, LIST_HINT);
+        if puzzles.is_empty() {
+            draw_text(
+                &format!("No puzzles found in {}/", PUZZLE_DIR),
+                30.0,
+                180.0,
+                24.0,
+                GRAY,
+            );
+        }
+    }
+
+    /// Draws the opener list with the number of pieces of each opener.
+    ///
+    /// # Arguments
+    /// * `openers` - The loaded openers
+    pub fn draw_openers(&self, openers: &[Opener]) {
+        let items: Vec<(String, Color)> = openers
+            .iter()
+            .map(|opener| (format!("{} ({} pieces)", opener.id, opener.steps.len()), WHITE))
+            .collect();
+        draw_list("Openers", &items, self.selected, LIST_HINT);
+        if openers.is_empty() {
+            draw_text(
+                &format!("No openers found in {}/", OPENER_DIR),
+                30.0,
+                180.0,
+                24.0,
+                GRAY,
+            );
+        }
+    }
+
+    /// Draws the settings screen, one "Label: < value >" row per setting.
+    ///
+    /// # Arguments
+    /// * `settings` - The settings being edited
+    pub fn draw_settings(&self, settings: &Settings) {
+        let items: Vec<(String, Color)> = settings
+            .options()
+            .into_iter()
+            .map(|(label, value)| (format!("{}: < {} >", label, value), WHITE))
+            .collect();
+        draw_list(
+            "Settings",
+            &items,
+            self.selected,
+            "Left/Right: change   Enter: save   Backspace: back",
+        );
+    }
+
+    /// Draws the profile list, marking the profile in use, with an entry to create a new one last.
+    ///
+    /// # Arguments
+    /// * `profiles` - The local profiles
+    pub fn draw_profiles(&self, profiles: &Profiles) {
+        let current = profiles.current();
+        let mut items: Vec<(String, Color)> = profiles
+            .names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                if i == current {
+                    (format!("{} (last used)", name), GOLD)
+                } else {
+                    (name.clone(), WHITE)
+                }
+            })
+            .collect();
+        items.push(("+ New profile".to_owned(), GREEN));
+        draw_list("Who's playing?", &items, self.selected, "Up/Down: select   Enter: play");
+    }
+
+    /// Draws the controls screen: the keys of every action, with conflicting actions in red.
+    ///
+    /// # Arguments
+    /// * `bindings` - The key bindings being edited
+    /// * `capturing` - Whether the next key press is added to the selected action
+    pub fn draw_controls(&self, bindings: &Bindings, capturing: bool) {
+        let items: Vec<(String, Color)> = Action::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, action)| {
+                let keys = if capturing && i == self.selected {
+                    "press a key...".to_owned()
+                } else {
+                    bindings.describe(*action)
+                };
+                let color = if bindings.has_conflict(*action) { RED } else { WHITE };
+                (format!("{}: {}", action.name(), keys), color)
+            })
+            .collect();
+        draw_list(
+            "Controls",
+            &items,
+            self.selected,
+            if capturing {
+                "Esc: cancel"
+            } else {
+                "Enter: add  Del: clear  Tab: preset  S: save"
+            },
+        );
+    }
+}
+
+/// Moves an index by `step` within `0..len`, wrapping around.
+fn cycle(idx: usize, step: i32, len: usize) -> usize {
+    (idx as i32 + step).rem_euclid(len as i32) as usize
+}
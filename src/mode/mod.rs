@@ -0,0 +1,464 @@
+// This module defines the GameMode enum, listing the rule variants the player can choose from
+// the start menu.
+//
+// Principles and design choices:
+//
+// - Each GameMode variant is a self-contained rule set; the engine queries the mode when a
+//   tetromino locks or spawns instead of scattering mode checks through the main loop.
+// - Variants carry their own options (e.g. the Sprint line goal), so the engine matches on the
+//   mode to read them and the start menu edits them through options()/change_option().
+// - The ALL constant keeps the menu order and default options in one place, and next()/prev()
+//   cycle through it.
+// - Designed for easy extension: adding a mode means adding a variant, a name and its rules.
+
+use std::mem::discriminant;
+
+use crate::classic::MAX_START_LEVEL;
+use crate::daily::DAILY_SECONDS;
+use crate::perfect_clear::PC_PIECES;
+
+/// Line goals selectable for Sprint mode.
+const SPRINT_LINES: [u32; 3] = [20, 40, 100];
+
+/// Time limits (in seconds) selectable for Ultra mode.
+const ULTRA_SECONDS: [u32; 4] = [60, 120, 180, 300];
+
+/// Line goals selectable for Marathon mode; any other goal up to MARATHON_MAX_LINES can be typed.
+const MARATHON_LINES: [u32; 5] = [100, 150, 200, 300, 500];
+
+/// Largest custom line goal for Marathon mode.
+const MARATHON_MAX_LINES: u32 = 999;
+
+/// Level caps selectable for Marathon mode.
+const MARATHON_LEVEL_CAPS: [u32; 2] = [15, 20];
+
+/// Garbage row counts selectable for Dig mode.
+const DIG_ROWS: [u32; 3] = [5, 10, 18];
+
+/// Cheese messiness selectable for Dig and Survival: the chance, in percent, that a garbage row's
+/// hole moves away from the hole of the row below (0 = a clean well, 100 = a new column every row).
+const CHEESE_MESSINESS: [u32; 4] = [0, 30, 60, 100];
+
+/// Seconds between rising garbage rows at the start of a Survival game.
+const SURVIVAL_INTERVALS: [u32; 3] = [3, 5, 8];
+
+/// Enum representing the available game modes.
+///  - Endless: The classic game, speeding up over time until the stack tops out.
+///  - Cascade: After a clear, connected blocks fall as units and can trigger chain clears.
+///  - Big: Every tetromino block covers 2x2 cells and pieces move in two-cell steps.
+///  - Sprint: Clear the given number of lines as fast as possible, optionally restarting on every
+///    finesse fault (judged on hard drop placements from the spawn height only).
+///  - Ultra: Score as much as possible before the time limit (in seconds) runs out.
+///  - Marathon: Level up every 10 lines (up to the level cap) and win by clearing the line goal (a
+///    preset or a custom number), optionally continuing endlessly after the goal is cleared.
+///  - Dig: Start on top of garbage rows (clean, partly or fully messy cheese) and dig through them as fast as possible;
+///    with regenerate on, garbage is refilled as you dig until the stack tops out (dig survival).
+///  - Zen: A relaxed game with no game over, no timer and no speed-up; when the stack reaches the top,
+///    bottom rows are removed instead.
+///  - Survival: Garbage rows rise from the bottom on a timer (starting every `interval` seconds) that
+///    speeds up over time; survive as long as possible.
+///  - Master: Arcade-style grading challenge: the level rises with every piece and line up to 999,
+///    gravity reaches 20G, the delays shorten and a grade is awarded from the score.
+///  - Classic: The NES ruleset, starting at the selected level: NES gravity and scoring, no hold,
+///    no wall kicks, a single next piece and the NES randomizer.
+///  - Combo: A 4-wide combo trainer: a 4-column well between filled walls, starting from a random
+///    residue; play until the well tops out, chaining as many line clears as possible.
+///  - Daily: A score attack with a piece sequence seeded from today's date; the first game of the day
+///    is scored, later ones are practice.
+///  - Practice: A sandbox with undo, board reset, a piece picker and gravity that can be switched off.
+///  - Opener: Drill a known opener: the pieces come in the opener's order and each one must lock on the
+///    translucent target shown on the board.
+///  - PerfectClear: Clear the empty board with the given number of pieces from a random bag; the results
+///    tell whether a perfect clear was possible and show a solution.
+///  - Puzzle: A fixed board and piece sequence loaded from a puzzle file, with a goal to reach
+///    before the pieces run out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    Endless,
+    Cascade,
+    Big,
+    Sprint { lines: u32, finesse_restart: bool },
+    Ultra { seconds: u32 },
+    Marathon { lines: u32, level_cap: u32, endless: bool },
+    Dig { rows: u32, messiness: u32, regenerate: bool },
+    Zen,
+    Survival { interval: u32, messiness: u32 },
+    Master,
+    Classic { start_level: u32 },
+    Combo,
+    Daily,
+    Practice,
+    Opener,
+    PerfectClear { pieces: u32 },
+    Puzzle,
+}
+
+/// Implementation of GameMode methods.
+impl GameMode {
+    /// All game modes with their default options, in the order they appear in the start menu.
+    pub const ALL: [GameMode; 17] = [
+        GameMode::Endless,
+        GameMode::Cascade,
+        GameMode::Big,
+        GameMode::Sprint { lines: 40, finesse_restart: false },
+        GameMode::Ultra { seconds: 120 },
+        GameMode::Marathon { lines: 150, level_cap: 15, endless: false },
+        GameMode::Dig { rows: 10, messiness: 100, regenerate: false },
+        GameMode::Zen,
+        GameMode::Survival { interval: 5, messiness: 0 },
+        GameMode::Master,
+        GameMode::Classic { start_level: 0 },
+        GameMode::Combo,
+        GameMode::Daily,
+        GameMode::Practice,
+        GameMode::Opener,
+        GameMode::PerfectClear { pieces: 10 },
+        GameMode::Puzzle,
+    ];
+
+    /// Returns the display name of the mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "Endless",
+            GameMode::Cascade => "Cascade",
+            GameMode::Big => "Big",
+            GameMode::Sprint { .. } => "Sprint",
+            GameMode::Ultra { .. } => "Ultra",
+            GameMode::Marathon { .. } => "Marathon",
+            GameMode::Dig { .. } => "Dig",
+            GameMode::Zen => "Zen",
+            GameMode::Survival { .. } => "Survival",
+            GameMode::Master => "Master",
+            GameMode::Classic { .. } => "Classic",
+            GameMode::Combo => "Combo",
+            GameMode::Daily => "Daily",
+            GameMode::Practice => "Practice",
+            GameMode::Opener => "Opener",
+            GameMode::PerfectClear { .. } => "Perfect Clear",
+            GameMode::Puzzle => "Puzzle",
+        }
+    }
+
+    /// Returns the next mode in menu order (with default options), wrapping around.
+    pub fn next(&self) -> GameMode {
+        let idx = self.menu_index();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// Returns the previous mode in menu order (with default options), wrapping around.
+    pub fn prev(&self) -> GameMode {
+        let idx = self.menu_index();
+        Self::ALL[(idx + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    /// Returns the position of the mode in ALL, ignoring its options.
+    fn menu_index(&self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|m| discriminant(m) == discriminant(self))
+            .unwrap_or(0)
+    }
+
+    /// Returns the label and current value of each of the mode's options, for the start menu.
+    pub fn options(&self) -> Vec<(&'static str, String)> {
+        match self {
+            GameMode::Sprint { lines, finesse_restart } => vec![
+                ("Lines", lines.to_string()),
+                ("Finesse restart", on_off(*finesse_restart)),
+            ],
+            GameMode::Ultra { seconds } => {
+                vec![("Time", format!("{}:{:02}", seconds / 60, seconds % 60))]
+            }
+            GameMode::Marathon { lines, level_cap, endless } => vec![
+                ("Lines", lines.to_string()),
+                ("Level cap", level_cap.to_string()),
+                ("Endless", on_off(*endless)),
+            ],
+            GameMode::Dig { rows, messiness, regenerate } => vec![
+                ("Garbage", rows.to_string()),
+                ("Cheese", cheese_name(*messiness)),
+                ("Regenerate", on_off(*regenerate)),
+            ],
+            GameMode::Survival { interval, messiness } => vec![
+                ("Rise every", format!("{}s", interval)),
+                ("Cheese", cheese_name(*messiness)),
+            ],
+            GameMode::Classic { start_level } => vec![("Start level", start_level.to_string())],
+            GameMode::PerfectClear { pieces } => vec![("Pieces", pieces.to_string())],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Cycles the value of the option at `index` forwards (1) or backwards (-1).
+    pub fn change_option(&mut self, index: usize, step: i32) {
+        match (self, index) {
+            (GameMode::Sprint { lines, .. }, 0) => {
+                *lines = cycle_value(*lines, &SPRINT_LINES, step)
+            }
+            (GameMode::Sprint { finesse_restart, .. }, 1) => *finesse_restart = !*finesse_restart,
+            (GameMode::Ultra { seconds }, 0) => {
+                *seconds = cycle_value(*seconds, &ULTRA_SECONDS, step)
+            }
+            (GameMode::Marathon { lines, .. }, 0) => {
+                *lines = cycle_value(*lines, &MARATHON_LINES, step)
+            }
+            (GameMode::Marathon { level_cap, .. }, 1) => {
+                *level_cap = cycle_value(*level_cap, &MARATHON_LEVEL_CAPS, step)
+            }
+            (GameMode::Marathon { endless, .. }, 2) => *endless = !*endless,
+            (GameMode::Dig { rows, .. }, 0) => *rows = cycle_value(*rows, &DIG_ROWS, step),
+            (GameMode::Dig { messiness, .. }, 1) => {
+                *messiness = cycle_value(*messiness, &CHEESE_MESSINESS, step)
+            }
+            (GameMode::Dig { regenerate, .. }, 2) => *regenerate = !*regenerate,
+            (GameMode::Survival { interval, .. }, 0) => {
+                *interval = cycle_value(*interval, &SURVIVAL_INTERVALS, step)
+            }
+            (GameMode::Survival { messiness, .. }, 1) => {
+                *messiness = cycle_value(*messiness, &CHEESE_MESSINESS, step)
+            }
+            (GameMode::Classic { start_level }, 0) => {
+                *start_level =
+                    (*start_level as i32 + step).rem_euclid(MAX_START_LEVEL as i32 + 1) as u32
+            }
+            (GameMode::PerfectClear { pieces }, 0) => {
+                *pieces = cycle_value(*pieces, &PC_PIECES, step)
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the option at `index` also takes a number typed on the keyboard (Marathon's line goal).
+    pub fn has_typed_option(&self, index: usize) -> bool {
+        matches!((self, index), (GameMode::Marathon { .. }, 0))
+    }
+
+    /// Types a digit into a numeric option, or erases its last digit.
+    /// A digit that would take the value past its maximum starts a new number, and the value never
+    /// drops below 1.
+    ///
+    /// # Arguments
+    /// * `index` - The index of the option
+    /// * `digit` - The digit typed, or `None` to erase the last digit
+    pub fn type_option(&mut self, index: usize, digit: Option<u32>) {
+        if let (GameMode::Marathon { lines, .. }, 0) = (self, index) {
+            *lines = match digit {
+                Some(digit) if *lines * 10 + digit <= MARATHON_MAX_LINES => *lines * 10 + digit,
+                Some(digit) => digit,
+                None => *lines / 10,
+            }
+            .max(1);
+        }
+    }
+
+    /// Whether blocks fall as connected units after a line clear.
+    pub fn has_cascade(&self) -> bool {
+        matches!(self, GameMode::Cascade)
+    }
+
+    /// Number of grid cells covered by each tetromino block along each axis.
+    pub fn piece_scale(&self) -> i32 {
+        match self {
+            GameMode::Big => 2,
+            _ => 1,
+        }
+    }
+
+    /// Number of lines that completes the game, if the mode has a line goal.
+    pub fn line_goal(&self) -> Option<u32> {
+        match self {
+            GameMode::Sprint { lines, .. } | GameMode::Marathon { lines, .. } => Some(*lines),
+            _ => None,
+        }
+    }
+
+    /// Whether play continues after the line goal is cleared.
+    pub fn continues_after_goal(&self) -> bool {
+        matches!(self, GameMode::Marathon { endless: true, .. })
+    }
+
+    /// Whether the play time is shown in the side panel.
+    pub fn shows_time(&self) -> bool {
+        matches!(
+            self,
+            GameMode::Sprint { .. }
+                | GameMode::Ultra { .. }
+                | GameMode::Marathon { .. }
+                | GameMode::Dig { .. }
+                | GameMode::Survival { .. }
+                | GameMode::Master
+                | GameMode::Daily
+        )
+    }
+
+    /// Whether split times are shown during play.
+    pub fn has_splits(&self) -> bool {
+        matches!(self, GameMode::Sprint { .. })
+    }
+
+    /// Highest level reachable when the level is driven by lines cleared (one level per 10 lines),
+    /// or None when the mode uses the clock-driven level progression instead.
+    pub fn line_level_cap(&self) -> Option<usize> {
+        match self {
+            GameMode::Marathon { level_cap, .. } => Some(*level_cap as usize),
+            _ => None,
+        }
+    }
+
+    /// Time limit in seconds that ends the game, if the mode has one.
+    pub fn time_limit(&self) -> Option<f64> {
+        match self {
+            GameMode::Ultra { seconds } => Some(*seconds as f64),
+            GameMode::Daily => Some(DAILY_SECONDS as f64),
+            _ => None,
+        }
+    }
+
+    /// Seconds between rising garbage rows at the start of the game, if garbage rises on a timer.
+    pub fn rise_interval(&self) -> Option<f32> {
+        match self {
+            GameMode::Survival { interval, .. } => Some(*interval as f32),
+            _ => None,
+        }
+    }
+
+    /// Whether the level (and fall speed) increases every 30 seconds.
+    /// Timed score attacks keep gravity fixed so every run is comparable,
+    /// line-driven modes level up from lines cleared instead,
+    /// and rising garbage modes speed up the garbage rather than gravity.
+    pub fn has_level_progression(&self) -> bool {
+        !matches!(
+            self,
+            GameMode::Ultra { .. }
+                | GameMode::Daily
+                | GameMode::Zen
+                | GameMode::Practice
+                | GameMode::Master
+                | GameMode::Classic { .. }
+                | GameMode::Combo
+                | GameMode::Opener
+                | GameMode::PerfectClear { .. }
+                | GameMode::Puzzle
+        )
+            && self.line_level_cap().is_none()
+            && self.rise_interval().is_none()
+    }
+
+    /// Whether the game ends when a new piece cannot spawn.
+    /// Without top out, bottom rows are removed until the piece fits.
+    pub fn has_top_out(&self) -> bool {
+        !matches!(self, GameMode::Zen)
+    }
+
+    /// Whether the player can hold the active tetromino.
+    /// Master and Classic follow the arcade and NES rules, which have no hold;
+    /// openers are placed in the order of their steps.
+    pub fn has_hold(&self) -> bool {
+        !matches!(self, GameMode::Master | GameMode::Classic { .. } | GameMode::Opener)
+    }
+
+    /// Whether a blocked rotation may shift the piece sideways to fit (wall kicks).
+    pub fn has_wall_kicks(&self) -> bool {
+        !matches!(self, GameMode::Classic { .. })
+    }
+
+    /// Whether placements can be undone, which needs an engine snapshot before every placement.
+    pub fn has_undo(&self) -> bool {
+        matches!(self, GameMode::Practice)
+    }
+
+    /// Whether the game can be rewound, which needs an engine snapshot at the start of every frame.
+    pub fn has_rewind(&self) -> bool {
+        matches!(self, GameMode::Practice | GameMode::Puzzle)
+    }
+
+    /// Whether every lock is checked for finesse faults. Big pieces and the Master and Classic
+    /// rules (20G, no kicks, different DAS) are not judged. Only placements a hard drop from the
+    /// spawn height can reach are judged; tucks, spins and soft drop slides never count as faults.
+    pub fn has_finesse(&self) -> bool {
+        !matches!(self, GameMode::Big | GameMode::Master | GameMode::Classic { .. })
+    }
+
+    /// Whether a finesse fault ends the game so a new one can start at once.
+    pub fn restarts_on_fault(&self) -> bool {
+        matches!(self, GameMode::Sprint { finesse_restart: true, .. })
+    }
+
+    /// Whether the game is played in the 4-wide combo well, with the combo stats in the side panel.
+    pub fn has_combo_well(&self) -> bool {
+        matches!(self, GameMode::Combo)
+    }
+
+    /// Whether the mode always deals the standard tetrominoes, whatever piece set is selected: the
+    /// combo well is sized for them, and daily games, puzzles, openers and perfect clear drills are
+    /// written or solved for them.
+    pub fn has_fixed_pieces(&self) -> bool {
+        matches!(
+            self,
+            GameMode::Combo | GameMode::Daily | GameMode::Puzzle | GameMode::Opener | GameMode::PerfectClear { .. }
+        )
+    }
+
+    /// Whether games are compared with other games, in the high score tables or as the daily
+    /// challenge, and so always fall at the built-in speed of their difficulty.
+    pub fn is_ranked(&self) -> bool {
+        self.has_high_scores() || *self == GameMode::Daily
+    }
+
+    /// Whether finished games enter the high score tables. Practice, trainers and puzzles have no
+    /// comparable result, Zen never ends and the daily challenge keeps its own history.
+    pub fn has_high_scores(&self) -> bool {
+        !matches!(
+            self,
+            GameMode::Zen
+                | GameMode::Daily
+                | GameMode::Practice
+                | GameMode::Opener
+                | GameMode::PerfectClear { .. }
+                | GameMode::Puzzle
+        )
+    }
+
+    /// Whether high scores rank completed games by time rather than by score.
+    pub fn ranks_by_time(&self) -> bool {
+        matches!(self, GameMode::Sprint { .. } | GameMode::Dig { regenerate: false, .. })
+    }
+
+    /// Starting level of modes with a level select (Classic).
+    pub fn start_level(&self) -> Option<u32> {
+        match self {
+            GameMode::Classic { start_level } => Some(*start_level),
+            _ => None,
+        }
+    }
+}
+
+/// Returns "On" or "Off" for a boolean option.
+fn on_off(value: bool) -> String {
+    if value { "On" } else { "Off" }.to_owned()
+}
+
+/// Formats a cheese messiness option; the ends keep the names of the former clean/messy switch, so
+/// their high score tables carry over.
+fn cheese_name(messiness: u32) -> String {
+    match messiness {
+        0 => "Clean".to_owned(),
+        100.. => "Messy".to_owned(),
+        percent => format!("{}% messy", percent),
+    }
+}
+
+/// Moves `value` to the next (or previous) entry of `choices`, wrapping around.
+/// Values not in the list restart from the first choice.
+fn cycle_value(value: u32, choices: &[u32], step: i32) -> u32 {
+    let Some(idx) = choices.iter().position(|&c| c == value) else {
+        // A custom value moves to the nearest choice in the direction of the step
+        let next = if step > 0 {
+            choices.iter().find(|&&c| c > value)
+        } else {
+            choices.iter().rev().find(|&&c| c < value)
+        };
+        return next.copied().unwrap_or(if step > 0 { choices[0] } else { choices[choices.len() - 1] });
+    };
+    choices[(idx as i32 + step).rem_euclid(choices.len() as i32) as usize]
+}
@@ -0,0 +1,234 @@
+// This module defines openers: known first-bag (or longer) setups drilled step by step, loaded from
+// TOML files so players can add their own.
+//
+// Principles and design choices:
+//
+// - An opener is a list of steps, one per piece, each giving the exact cells the piece must lock on.
+//   The piece sequence is taken from the steps, so the bag order always matches the setup.
+// - Each step target is written like a puzzle board, as rows aligned to the bottom of the grid, using
+//   the piece letter for the target cells and '.' elsewhere. Targets describe the board as it is when
+//   the piece locks, after the lines cleared by earlier steps, so openers with clears need no special case.
+// - Files are parsed into plain serde structs and validated into Opener values, so a broken file
+//   produces a readable error message instead of a panic.
+// - Each target must be one of the orientations of its piece, as listed by the perfect clear solver,
+//   so a typo in a target is reported when the file loads rather than making the step impossible.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::parameters::{GRID_HEIGHT, GRID_WIDTH};
+use crate::perfect_clear::orientations;
+use crate::piece_set::PieceSet;
+use crate::puzzle::piece_index;
+
+/// Directory scanned for opener files (relative to the working directory).
+pub const OPENER_DIR: &str = "openers";
+
+/// One piece of an opener and the cells it must lock on.
+#[derive(Debug, Clone)]
+pub struct OpenerStep {
+    /// Piece index into the standard piece set.
+    pub piece: usize,
+    /// Target cells as (row, column) grid positions.
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// A single opener, validated and ready to play.
+#[derive(Debug, Clone)]
+pub struct Opener {
+    /// "file/opener" name shown in the list.
+    pub id: String,
+    pub steps: Vec<OpenerStep>,
+}
+
+/// Implementation of Opener methods.
+impl Opener {
+    /// Returns the piece sequence of the opener, one piece per step.
+    pub fn sequence(&self) -> Vec<usize> {
+        self.steps.iter().map(|step| step.piece).collect()
+    }
+}
+
+/// Raw opener file as written on disk.
+#[derive(Deserialize)]
+struct OpenerFile {
+    name: String,
+    opener: Vec<OpenerEntry>,
+}
+
+/// Raw opener entry as written on disk.
+#[derive(Deserialize)]
+struct OpenerEntry {
+    name: String,
+    step: Vec<StepEntry>,
+}
+
+/// Raw opener step as written on disk.
+#[derive(Deserialize)]
+struct StepEntry {
+    target: Vec<String>,
+}
+
+/// Parses and validates an opener file from TOML text.
+///
+/// # Arguments
+/// * `text` - The contents of an opener file
+/// # Returns
+/// * The openers of the file, or an error message describing the first problem found.
+pub fn parse_opener_file(text: &str) -> Result<Vec<Opener>, String> {
+    let file: OpenerFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    let standard = PieceSet::standard();
+    file.opener
+        .into_iter()
+        .map(|entry| {
+            let steps = entry
+                .step
+                .iter()
+                .enumerate()
+                .map(|(i, step)| {
+                    parse_step(&step.target, &standard)
+                        .map_err(|e| format!("opener {}, step {}: {}", entry.name, i + 1, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if steps.is_empty() {
+                return Err(format!("opener {}: no steps", entry.name));
+            }
+            Ok(Opener {
+                id: format!("{}/{}", file.name, entry.name),
+                steps,
+            })
+        })
+        .collect()
+}
+
+/// Parses the target of one step: rows aligned to the bottom of the grid, with the cells of a single
+/// piece written as its letter.
+fn parse_step(rows: &[String], standard: &PieceSet) -> Result<OpenerStep, String> {
+    if rows.len() > GRID_HEIGHT {
+        return Err(format!("target has {} rows, at most {} fit", rows.len(), GRID_HEIGHT));
+    }
+    let top = GRID_HEIGHT - rows.len();
+    let mut piece = None;
+    let mut cells = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() > GRID_WIDTH {
+            return Err(format!("target row {:?} is wider than {} cells", row, GRID_WIDTH));
+        }
+        for (x, c) in row.chars().enumerate() {
+            if c == '.' || c == ' ' {
+                continue;
+            }
+            let idx =
+                piece_index(standard, c).ok_or_else(|| format!("unknown target cell {:?}", c))?;
+            if piece.is_some_and(|p| p != idx) {
+                return Err("target mixes several pieces".to_owned());
+            }
+            piece = Some(idx);
+            cells.push((top + y, x));
+        }
+    }
+    let piece = piece.ok_or_else(|| "empty target".to_owned())?;
+    // The cells must form one of the piece's orientations, compared from the lowest row and leftmost column
+    let lowest = cells.iter().map(|c| c.0).max().unwrap_or(0);
+    let leftmost = cells.iter().map(|c| c.1).min().unwrap_or(0);
+    let mut shape: Vec<(usize, usize)> = cells.iter().map(|&(row, col)| (lowest - row, col - leftmost)).collect();
+    shape.sort();
+    if !orientations(standard)[piece].iter().any(|orientation| orientation.cells == shape) {
+        return Err(format!("target cells do not form a {} piece", standard.pieces[piece].name));
+    }
+    Ok(OpenerStep { piece, cells })
+}
+
+/// Loads every valid opener file found in OPENER_DIR, in file name order.
+/// Files that fail to load are skipped with a message on standard error.
+pub fn load_openers() -> Vec<Opener> {
+    let Ok(entries) = fs::read_dir(Path::new(OPENER_DIR)) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    let mut openers = Vec::new();
+    for path in paths {
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_opener_file(&text))
+        {
+            Ok(file) => openers.extend(file),
+            Err(e) => eprintln!("Skipping opener file {}: {}", path.display(), e),
+        }
+    }
+    openers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a file holding a single opener with one step per target.
+    fn parse_targets(targets: &[&[&str]]) -> Result<Vec<Opener>, String> {
+        let mut text = "name = \"Test\"\n[[opener]]\nname = \"Test\"\n".to_owned();
+        for target in targets {
+            let rows: Vec<String> = target.iter().map(|row| format!("{:?}", row)).collect();
+            text.push_str(&format!("[[opener.step]]\ntarget = [{}]\n", rows.join(", ")));
+        }
+        parse_opener_file(&text)
+    }
+
+    /// Returns the piece letters of an opener's sequence.
+    fn letters(opener: &Opener) -> String {
+        let standard = PieceSet::standard();
+        opener.sequence().iter().map(|&piece| standard.pieces[piece].name.as_str()).collect()
+    }
+
+    #[test]
+    fn bundled_openers_parse() {
+        let openers = parse_opener_file(include_str!("../../openers/openers.toml")).unwrap();
+        let ids: Vec<&str> = openers.iter().map(|opener| opener.id.as_str()).collect();
+        assert_eq!(ids, ["Openers/Perfect clear", "Openers/Perfect clear (mirrored)"]);
+        assert_eq!(letters(&openers[0]), "TIOLJSZIOT");
+        for opener in &openers {
+            assert!(opener.steps.iter().all(|step| step.cells.len() == 4));
+        }
+    }
+
+    #[test]
+    fn target_rows_align_to_the_bottom() {
+        let openers = parse_targets(&[&["...T......", "..TTT....."]]).unwrap();
+        let bottom = GRID_HEIGHT - 1;
+        assert_eq!(openers[0].steps[0].cells, [(bottom - 1, 3), (bottom, 2), (bottom, 3), (bottom, 4)]);
+    }
+
+    #[test]
+    fn every_orientation_is_accepted() {
+        let targets: [&[&str]; 4] = [&["LLL.", "L..."], &["LL", ".L", ".L"], &["..L", "LLL"], &["L.", "L.", "LL"]];
+        for target in targets {
+            assert!(parse_targets(&[target]).is_ok(), "{:?}", target);
+        }
+    }
+
+    #[test]
+    fn cells_off_the_piece_shape_are_refused() {
+        // Four L cells, but not an L
+        let err = parse_targets(&[&["LL..", "..LL"]]).unwrap_err();
+        assert!(err.contains("do not form a L piece"), "{}", err);
+        // The mirror of an S is a Z
+        assert!(parse_targets(&[&["SS.", ".SS"]]).is_err());
+        // Disconnected cells
+        assert!(parse_targets(&[&["T.T", ".T.", ".T."]]).is_err());
+    }
+
+    #[test]
+    fn broken_targets_are_refused() {
+        assert!(parse_targets(&[&["TTT", ".T."], &["..."]]).unwrap_err().contains("step 2: empty target"));
+        assert!(parse_targets(&[&["TTT", ".O."]]).unwrap_err().contains("several pieces"));
+        assert!(parse_targets(&[&["TTTT"]]).is_err());
+        assert!(parse_targets(&[&["XXXX"]]).unwrap_err().contains("unknown target cell"));
+        assert!(parse_targets(&[&["IIII......."]]).unwrap_err().contains("wider"));
+        assert!(parse_targets(&[]).is_err());
+    }
+}
@@ -3,11 +3,16 @@
 // Principles and design choices:
 //
 // - Centralizes all grid, block, and panel sizing constants for easy tuning and consistency.
+// - The constants are the defaults of the player's settings (see the settings module); the block size
+//   in use is fixed once when the window opens and read through block_size(). The built-in fall delays
+//   stay in use for ranked games, so high scores and the daily challenge compare games at one speed.
 // - Defines timing parameters for movement and soft drop logic, allowing smooth and configurable controls.
 // - Groups movement timers in a Timers struct for cleaner state management.
 // - Implements the Difficulty enum and logic, enabling adjustable game speed and difficulty selection.
 // - Designed for clarity, maintainability, and straightforward integration with the rest of the game.
 
+use std::sync::OnceLock;
+
 /// Width of the Tetris grid (number of columns).
 pub const GRID_WIDTH: usize = 10;
 
@@ -17,13 +22,29 @@ pub const GRID_HEIGHT: usize = 20;
 /// Width of the score panel.
 pub const SCORE_WIDTH: f32 = 160.0;
 
-/// Size of a single Tetris block (in pixels).
+/// Default size of a single Tetris block (in pixels).
 pub const BLOCK_SIZE: f32 = 30.0;
 
-/// Delay (in seconds) between soft drop moves when holding the down key.
+/// Block size chosen when the window opened.
+static BLOCK_SIZE_IN_USE: OnceLock<f32> = OnceLock::new();
+
+/// Returns the size of a single Tetris block (in pixels) for this run.
+pub fn block_size() -> f32 {
+    *BLOCK_SIZE_IN_USE.get_or_init(|| BLOCK_SIZE)
+}
+
+/// Sets the block size for this run; only the first call has an effect, before anything is drawn.
+///
+/// # Arguments
+/// * `size` - The block size, in pixels
+pub fn init_block_size(size: f32) {
+    let _ = BLOCK_SIZE_IN_USE.set(size);
+}
+
+/// Default delay (in seconds) between soft drop moves when holding the down key.
 pub const SOFT_DROP_DELAY_VERTICAL: f32 = 0.05;
 
-/// Delay (in seconds) between left/right moves when holding the left or right key.
+/// Default delay (in seconds) between left/right moves when holding the left or right key.
 pub const SOFT_DROP_DELAY_HORIZONTAL: f32 = 0.15;
 
 /// Struct to group all movement timers for soft drop logic.
@@ -58,7 +79,21 @@ pub enum Difficulty {
 
 /// Implementation of Difficulty methods.
 impl Difficulty {
-    pub fn fall_delay(&self) -> f32 {
+    /// All difficulties, in the order they appear in the start menu.
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    /// Returns the display name of the difficulty.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    /// Returns the built-in fall delay (in seconds per row) of the difficulty. Ranked games always
+    /// fall at this speed; the settings can change it for unranked play only.
+    pub const fn fall_delay(&self) -> f32 {
         match self {
             Difficulty::Easy => 0.7,
             Difficulty::Medium => 0.5,
@@ -0,0 +1,407 @@
+// This module defines the perfect clear trainer: a random piece sequence to clear the board with, and a
+// solver that finds out whether a perfect clear was possible by hard drops and shows one solution.
+//
+// Principles and design choices:
+//
+// - A drill deals N standard pieces from a fresh bag onto an empty board; N pieces fill N * 4 / 10 lines
+//   (2 lines for 5 pieces, 4 lines for 10), and every piece must be used, with hold allowed.
+// - The solver places pieces by hard drop only (no tucks or spins) and never leaves a hole, so the board
+//   is fully described by its column heights and full rows are always the bottom ones. A depth-first
+//   search over the sequence (with hold, which lets the current or the held piece be placed) with a
+//   memo of dead ends answers in a fraction of a second, once per drill when the pieces are dealt.
+//   Boards where a stretch of empty cells walled off by full-height columns is not a multiple of 4
+//   cells can never be filled, and are cut off at once.
+// - Since tucks and spins are never tried, a failed search means no hard drop solution was found, not
+//   that no perfect clear exists, and the results say so.
+// - Solutions are reported in the coordinates of the original lines, as if no line had cleared, so the
+//   whole solution fits in one small picture with the pieces numbered in placement order.
+
+use std::collections::HashSet;
+
+use macroquad::prelude::*;
+
+use crate::piece_set::PieceSet;
+use crate::tetromino::{TetrominoBag, rotate};
+
+/// Piece counts selectable for the perfect clear trainer (2 and 4 lines).
+pub const PC_PIECES: [u32; 2] = [5, 10];
+
+/// Columns of the board.
+const WIDTH: usize = 10;
+
+/// One piece of a solution.
+#[derive(Debug, Clone)]
+pub struct SolvedPiece {
+    /// Piece index into the standard piece set.
+    pub piece: usize,
+    /// Cells as (line, column), lines counted from the bottom of the original board.
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// A perfect clear drill: the pieces dealt and the solver's verdict.
+#[derive(Debug, Clone)]
+pub struct PerfectClearDrill {
+    /// Piece indices into the standard piece set, in dealing order.
+    pub sequence: Vec<usize>,
+    /// Lines the pieces fill.
+    pub lines: usize,
+    /// One hard drop solution, if the solver found one.
+    pub solution: Option<Vec<SolvedPiece>>,
+}
+
+/// Implementation of PerfectClearDrill methods.
+impl PerfectClearDrill {
+    /// Deals a new drill of the given number of pieces from a fresh bag and solves it.
+    ///
+    /// # Arguments
+    /// * `count` - The number of pieces (5 or 10)
+    pub fn deal(count: u32) -> Self {
+        let standard = PieceSet::standard();
+        let mut bag = TetrominoBag::new(&standard.weights());
+        let sequence = (0..count).map(|_| bag.next()).collect();
+        Self::new(sequence)
+    }
+
+    /// Creates a drill for a known piece sequence and solves it.
+    ///
+    /// # Arguments
+    /// * `sequence` - The pieces, as indices into the standard piece set
+    pub fn new(sequence: Vec<usize>) -> Self {
+        let lines = sequence.len() * 4 / WIDTH;
+        let solution = solve(&sequence, lines);
+        Self { sequence, lines, solution }
+    }
+
+    /// Draws the solution as a small board with each piece in its color and numbered in placement order.
+    ///
+    /// # Arguments
+    /// * `x` - The left edge of the picture
+    /// * `y` - The top edge of the picture
+    /// * `cell` - The size of a cell, in pixels
+    pub fn draw_solution(&self, x: f32, y: f32, cell: f32) {
+        let Some(solution) = &self.solution else {
+            return;
+        };
+        let standard = PieceSet::standard();
+        let top = |line: usize| y + (self.lines - 1 - line) as f32 * cell;
+        draw_rectangle_lines(x - 2.0, y - 2.0, WIDTH as f32 * cell + 4.0, self.lines as f32 * cell + 4.0, 2.0, GRAY);
+        for (i, placed) in solution.iter().enumerate() {
+            let color = standard.pieces[placed.piece].color;
+            for &(line, col) in &placed.cells {
+                draw_rectangle(x + col as f32 * cell, top(line), cell - 1.0, cell - 1.0, color);
+            }
+            if let Some(&(line, col)) = placed.cells.first() {
+                draw_text(&(i + 1).to_string(), x + col as f32 * cell + 3.0, top(line) + cell - 4.0, cell, BLACK);
+            }
+        }
+    }
+}
+
+/// A distinct orientation of a piece, as cells (row up from the lowest cell, column from the leftmost).
+pub(crate) struct Orientation {
+    /// Cells sorted by row, then column.
+    pub(crate) cells: Vec<(usize, usize)>,
+    width: usize,
+    /// Lowest and highest row of the orientation in each of its columns.
+    bottom: Vec<usize>,
+    top: Vec<usize>,
+}
+
+/// Returns the distinct orientations of every standard piece.
+pub(crate) fn orientations(standard: &PieceSet) -> Vec<Vec<Orientation>> {
+    standard
+        .pieces
+        .iter()
+        .map(|piece| {
+            let mut seen: Vec<Vec<(usize, usize)>> = Vec::new();
+            let mut shape = piece.shape;
+            for _ in 0..4 {
+                let size = shape.size();
+                let filled: Vec<(usize, usize)> = (0..size)
+                    .flat_map(|row| (0..size).map(move |col| (row, col)))
+                    .filter(|&(row, col)| shape[row][col] != 0)
+                    .collect();
+                let max_row = filled.iter().map(|c| c.0).max().unwrap_or(0);
+                let min_col = filled.iter().map(|c| c.1).min().unwrap_or(0);
+                let mut cells: Vec<(usize, usize)> =
+                    filled.iter().map(|&(row, col)| (max_row - row, col - min_col)).collect();
+                cells.sort();
+                if !seen.contains(&cells) {
+                    seen.push(cells);
+                }
+                shape = rotate(&shape);
+            }
+            seen.into_iter()
+                .map(|cells| {
+                    let width = cells.iter().map(|c| c.1).max().unwrap_or(0) + 1;
+                    let column = |col: usize| cells.iter().filter(move |c| c.1 == col).map(|c| c.0);
+                    let bottom = (0..width).map(|col| column(col).min().unwrap_or(0)).collect();
+                    let top = (0..width).map(|col| column(col).max().unwrap_or(0)).collect();
+                    Orientation { cells, width, bottom, top }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// State of the search between two placements.
+struct Search<'a> {
+    sequence: &'a [usize],
+    orientations: Vec<Vec<Orientation>>,
+    /// States known to lead nowhere; the lines left follow from the heights and the pieces placed.
+    dead: HashSet<([usize; WIDTH], usize, Option<usize>)>,
+    path: Vec<SolvedPiece>,
+}
+
+/// Finds a way to place every piece of the sequence (with hold) that clears the given number of lines.
+///
+/// # Arguments
+/// * `sequence` - The pieces, as indices into the standard piece set
+/// * `lines` - The lines to clear
+/// # Returns
+/// * The placements of one solution, or `None` if no hard drop placement without holes clears the
+///   lines (a perfect clear with tucks or spins may still exist).
+pub fn solve(sequence: &[usize], lines: usize) -> Option<Vec<SolvedPiece>> {
+    if sequence.len() * 4 != lines * WIDTH {
+        return None;
+    }
+    let mut search = Search {
+        sequence,
+        orientations: orientations(&PieceSet::standard()),
+        dead: HashSet::new(),
+        path: Vec::new(),
+    };
+    search.place([0; WIDTH], lines, 0, 0, None).then_some(search.path)
+}
+
+/// Implementation of Search methods.
+impl Search<'_> {
+    /// Tries every placement of the current (or held) piece and recurses.
+    ///
+    /// # Arguments
+    /// * `heights` - The column heights
+    /// * `lines` - The lines left to clear
+    /// * `cleared` - The lines cleared so far, to report cells in original lines
+    /// * `index` - The next piece of the sequence
+    /// * `held` - The held piece
+    /// # Returns
+    /// * `true` once the board is clear with every piece used.
+    fn place(&mut self, heights: [usize; WIDTH], lines: usize, cleared: usize, index: usize, held: Option<usize>) -> bool {
+        if index == self.sequence.len() && held.is_none() {
+            return lines == 0;
+        }
+        let key = (heights, index, held);
+        if self.dead.contains(&key) {
+            return false;
+        }
+
+        // Candidates: (piece to place, next index, piece held afterwards)
+        let mut candidates = Vec::new();
+        match (self.sequence.get(index), held) {
+            (Some(&current), None) => {
+                candidates.push((current, index + 1, None));
+                if let Some(&next) = self.sequence.get(index + 1) {
+                    candidates.push((next, index + 2, Some(current)));
+                }
+            }
+            (Some(&current), Some(held)) => {
+                candidates.push((current, index + 1, Some(held)));
+                if held != current {
+                    candidates.push((held, index + 1, Some(current)));
+                }
+            }
+            (None, Some(held)) => candidates.push((held, index, None)),
+            (None, None) => {}
+        }
+
+        for (piece, next_index, next_held) in candidates {
+            for o in 0..self.orientations[piece].len() {
+                for x in 0..=WIDTH - self.orientations[piece][o].width {
+                    let orientation = &self.orientations[piece][o];
+                    // Landing row: the piece rests on the highest column under it
+                    let y = (0..orientation.width)
+                        .map(|col| heights[x + col].saturating_sub(orientation.bottom[col]))
+                        .max()
+                        .unwrap_or(0);
+                    // No holes under the piece, and nothing above the lines to clear
+                    let fits = (0..orientation.width)
+                        .all(|col| heights[x + col] == y + orientation.bottom[col])
+                        && (0..orientation.width).all(|col| y + orientation.top[col] < lines);
+                    if !fits {
+                        continue;
+                    }
+                    let mut next = heights;
+                    for col in 0..orientation.width {
+                        next[x + col] = y + orientation.top[col] + 1;
+                    }
+                    let full = next.iter().copied().min().unwrap_or(0);
+                    for height in next.iter_mut() {
+                        *height -= full;
+                    }
+                    if !fillable(&next, lines - full) {
+                        continue;
+                    }
+                    let cells = orientation
+                        .cells
+                        .iter()
+                        .map(|&(row, col)| (cleared + y + row, x + col))
+                        .collect();
+
+                    self.path.push(SolvedPiece { piece, cells });
+                    if self.place(next, lines - full, cleared + full, next_index, next_held) {
+                        return true;
+                    }
+                    self.path.pop();
+                }
+            }
+        }
+        self.dead.insert(key);
+        false
+    }
+}
+
+/// Whether every stretch of empty cells between full-height columns could be filled with whole pieces.
+///
+/// # Arguments
+/// * `heights` - The column heights
+/// * `lines` - The lines left to clear
+fn fillable(heights: &[usize; WIDTH], lines: usize) -> bool {
+    let mut empty = 0;
+    for &height in heights.iter().chain(std::iter::once(&lines)) {
+        if height == lines {
+            if empty % 4 != 0 {
+                return false;
+            }
+            empty = 0;
+        } else {
+            empty += lines - height;
+        }
+    }
+    true
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Turns piece letters into indices into the standard piece set.
+    fn pieces(letters: &str) -> Vec<usize> {
+        letters.chars().map(|c| "IOSZJLT".find(c).unwrap()).collect()
+    }
+
+    /// Checks that a solution covers every cell of the lines once, with pieces of the right shape
+    /// placed in an order that hold allows.
+    fn check_solution(sequence: &[usize], lines: usize, solution: &[SolvedPiece]) {
+        let orientations = orientations(&PieceSet::standard());
+        let mut covered = [[false; WIDTH]; 4];
+        for placed in solution {
+            for &(line, col) in &placed.cells {
+                assert!(line < lines && !covered[line][col], "cell {:?} overlaps or overflows", (line, col));
+                covered[line][col] = true;
+            }
+            let lowest = placed.cells.iter().map(|c| c.0).min().unwrap();
+            let leftmost = placed.cells.iter().map(|c| c.1).min().unwrap();
+            let mut shape: Vec<_> = placed.cells.iter().map(|&(line, col)| (line - lowest, col - leftmost)).collect();
+            shape.sort();
+            assert!(orientations[placed.piece].iter().any(|o| o.cells == shape), "{:?}", placed);
+        }
+        assert!(covered[..lines].iter().all(|line| line.iter().all(|&cell| cell)));
+
+        // Replay the order with hold: each piece is the current one, the held one, or the next one
+        // when nothing is held yet
+        let (mut index, mut held) = (0, None);
+        for placed in solution {
+            match (sequence.get(index), held) {
+                (Some(&current), _) if current == placed.piece => index += 1,
+                (Some(&current), Some(h)) if h == placed.piece => {
+                    held = Some(current);
+                    index += 1;
+                }
+                (Some(&current), None) if sequence.get(index + 1) == Some(&placed.piece) => {
+                    held = Some(current);
+                    index += 2;
+                }
+                (None, Some(h)) if h == placed.piece => held = None,
+                _ => panic!("piece {} cannot be placed at this point", placed.piece),
+            }
+        }
+        assert_eq!((index, held), (sequence.len(), None), "every piece is used");
+    }
+
+    #[test]
+    fn solvable_sequences() {
+        for (letters, lines) in [("OOOOO", 2), ("ILJOO", 2), ("OOLJI", 2), ("IIIIIIIIII", 4), ("TIOLJSZIOT", 4)] {
+            let sequence = pieces(letters);
+            let solution = solve(&sequence, lines).unwrap_or_else(|| panic!("{} has a solution", letters));
+            check_solution(&sequence, lines, &solution);
+        }
+    }
+
+    #[test]
+    fn unsolvable_sequences() {
+        for (letters, lines) in [("IIIII", 2), ("IIOLJ", 2), ("SOLJI", 2), ("SZSZSZSZSZ", 4), ("TTTTTTTTTT", 4)] {
+            assert!(solve(&pieces(letters), lines).is_none(), "{} has no solution", letters);
+        }
+        // The pieces must fill the lines exactly
+        assert!(solve(&pieces("OOOO"), 2).is_none());
+    }
+
+    #[test]
+    fn hold_lets_a_later_piece_go_first() {
+        // An S or Z on the empty floor always leaves a hole, so it has to wait in hold
+        for letters in ["SIOLJTZIOT", "ZIOLJTSIOT", "STIOLJZIOT"] {
+            let sequence = pieces(letters);
+            let solution = solve(&sequence, 4).unwrap_or_else(|| panic!("{} has a solution", letters));
+            assert_ne!(solution[0].piece, sequence[0]);
+            check_solution(&sequence, 4, &solution);
+        }
+    }
+
+    #[test]
+    fn dead_ends_are_remembered() {
+        let sequence = pieces("TTTTTTTTTT");
+        let mut search = Search {
+            sequence: &sequence,
+            orientations: orientations(&PieceSet::standard()),
+            dead: HashSet::new(),
+            path: Vec::new(),
+        };
+        assert!(!search.place([0; WIDTH], 4, 0, 0, None));
+        assert!(search.dead.contains(&([0; WIDTH], 0, None)));
+        let explored = search.dead.len();
+        // A second attempt from the same state is answered by the memo alone
+        assert!(!search.place([0; WIDTH], 4, 0, 0, None));
+        assert_eq!(search.dead.len(), explored);
+        assert!(search.path.is_empty());
+
+        // Dead ends found on the way do not hide a solution
+        let sequence = pieces("SIOLJTZIOT");
+        let mut search = Search {
+            sequence: &sequence,
+            orientations: orientations(&PieceSet::standard()),
+            dead: HashSet::new(),
+            path: Vec::new(),
+        };
+        assert!(search.place([0; WIDTH], 4, 0, 0, None));
+        assert!(!search.dead.is_empty());
+        assert_eq!(search.path.len(), sequence.len());
+    }
+
+    #[test]
+    fn fillable_stretches() {
+        assert!(fillable(&[0; WIDTH], 2));
+        assert!(fillable(&[0; WIDTH], 4));
+        // A 2x2 pit between full columns takes an O
+        assert!(fillable(&[2, 0, 0, 2, 2, 2, 2, 2, 2, 2], 2));
+        assert!(fillable(&[1, 1, 1, 1, 2, 2, 2, 2, 2, 2], 2));
+        // One empty column of two cells, walled off on both sides
+        assert!(!fillable(&[2, 0, 2, 2, 2, 2, 2, 2, 2, 2], 2));
+        // Three cells against the left wall
+        assert!(!fillable(&[1, 1, 1, 2, 2, 2, 2, 2, 2, 2], 2));
+        // Three cells against the right wall, after a stretch that would fit
+        assert!(!fillable(&[0, 0, 2, 2, 2, 2, 2, 2, 2, 1], 2));
+        // Only the cell counts are checked: a single column of four only takes an I
+        assert!(fillable(&[0, 4, 4, 4, 4, 4, 4, 4, 4, 4], 4));
+    }
+}
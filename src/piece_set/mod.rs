@@ -0,0 +1,237 @@
+// This module defines piece sets: the collection of shapes a game deals, with their colors,
+// spawn positions and randomizer weights.
+//
+// Principles and design choices:
+//
+// - The built-in standard set is made from the SHAPES and COLORS constants, so classic play needs no files.
+// - Additional sets (pentominoes, arbitrary polyominoes, party sets) are read from TOML files in the
+//   PIECE_SET_DIR directory. Shapes are written as rows of text where '#' marks a filled cell, which keeps
+//   the files easy to author by hand.
+// - Files are parsed into plain serde structs first and then validated into a PieceSet, so a broken
+//   file produces a readable error message instead of a panic.
+// - Every shape must fit in a MAX_SHAPE_SIZE x MAX_SHAPE_SIZE box; weights control how many copies of a
+//   piece go into each bag (see TetrominoBag).
+// - Spawn positions are checked when the file loads: every cell of the piece must spawn inside the grid,
+//   both at normal size and in Big mode, where each block covers 2x2 cells.
+// - Colors use the same "#rrggbb" parser as the settings file.
+
+use std::fs;
+use std::path::Path;
+
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+use crate::parameters::{GRID_HEIGHT, GRID_WIDTH};
+use crate::settings::parse_color;
+use crate::tetromino::{COLORS, SHAPES, TetrominoShape};
+
+/// Directory scanned for piece set files (relative to the working directory).
+pub const PIECE_SET_DIR: &str = "pieces";
+
+/// Names of the standard tetrominoes, in the same order as SHAPES.
+const STANDARD_NAMES: [&str; 7] = ["I", "O", "S", "Z", "J", "L", "T"];
+
+/// A single piece of a set.
+#[derive(Debug, Clone)]
+pub struct PieceDef {
+    pub name: String,
+    pub shape: TetrominoShape,
+    pub color: Color,
+    /// Spawn column in blocks, or None to center the piece.
+    pub spawn_x: Option<i32>,
+    /// Spawn row in blocks.
+    pub spawn_y: i32,
+    /// Number of copies of the piece in each bag.
+    pub weight: u32,
+}
+
+/// A named collection of pieces dealt by the randomizer.
+#[derive(Debug, Clone)]
+pub struct PieceSet {
+    pub name: String,
+    pub pieces: Vec<PieceDef>,
+}
+
+/// Raw piece set file as written on disk.
+#[derive(Deserialize)]
+struct PieceSetFile {
+    name: String,
+    piece: Vec<PieceEntry>,
+}
+
+/// Raw piece entry as written on disk.
+#[derive(Deserialize)]
+struct PieceEntry {
+    name: String,
+    color: String,
+    shape: Vec<String>,
+    weight: Option<u32>,
+    spawn_x: Option<i32>,
+    spawn_y: Option<i32>,
+}
+
+/// Implementation of PieceDef methods.
+impl PieceDef {
+    /// Returns the spawn column in blocks: the given one, or the piece centered in `columns`.
+    ///
+    /// # Arguments
+    /// * `columns` - The number of block columns of the grid
+    pub fn spawn_column(&self, columns: i32) -> i32 {
+        self.spawn_x.unwrap_or((columns - self.shape.size() as i32 + 1) / 2)
+    }
+
+    /// Whether every cell of the piece spawns inside the grid.
+    ///
+    /// # Arguments
+    /// * `scale` - The number of cells covered by each block along each axis
+    fn spawns_inside(&self, scale: i32) -> bool {
+        let (columns, rows) = (GRID_WIDTH as i32 / scale, GRID_HEIGHT as i32 / scale);
+        let (x, y) = (self.spawn_column(columns), self.spawn_y);
+        (0..self.shape.size()).all(|row| {
+            (0..self.shape.size()).all(|col| {
+                let (cx, cy) = (x + col as i32, y + row as i32);
+                self.shape[row][col] == 0 || ((0..columns).contains(&cx) && (0..rows).contains(&cy))
+            })
+        })
+    }
+}
+
+/// Implementation of PieceSet methods.
+impl PieceSet {
+    /// Returns the standard set of 7 tetrominoes.
+    pub fn standard() -> Self {
+        let pieces = (0..SHAPES.len())
+            .map(|idx| PieceDef {
+                name: STANDARD_NAMES[idx].to_owned(),
+                shape: SHAPES[idx],
+                color: COLORS[idx],
+                spawn_x: None,
+                spawn_y: 0,
+                weight: 1,
+            })
+            .collect();
+        Self {
+            name: "Standard".to_owned(),
+            pieces,
+        }
+    }
+
+    /// Parses and validates a piece set from TOML text.
+    ///
+    /// # Arguments
+    /// * `text` - The contents of a piece set file
+    /// # Returns
+    /// * The piece set, or an error message describing the first problem found.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let file: PieceSetFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        if file.piece.is_empty() {
+            return Err("piece set has no pieces".to_owned());
+        }
+        let mut pieces = Vec::with_capacity(file.piece.len());
+        for entry in file.piece {
+            let rows: Vec<Vec<u8>> = entry
+                .shape
+                .iter()
+                .map(|row| row.chars().map(|c| u8::from(c == '#')).collect())
+                .collect();
+            let shape = TetrominoShape::from_rows(&rows)
+                .map_err(|e| format!("piece {}: {}", entry.name, e))?;
+            let color = parse_color(&entry.color)
+                .ok_or_else(|| format!("piece {}: invalid color {:?}", entry.name, entry.color))?;
+            let piece = PieceDef {
+                name: entry.name,
+                shape,
+                color,
+                spawn_x: entry.spawn_x,
+                spawn_y: entry.spawn_y.unwrap_or(0),
+                weight: entry.weight.unwrap_or(1),
+            };
+            // The piece must spawn inside the grid, and inside the half-size grid of Big mode
+            for (scale, mode) in [(1, ""), (2, " in Big mode")] {
+                if !piece.spawns_inside(scale) {
+                    return Err(format!("piece {}: spawn position is outside the grid{}", piece.name, mode));
+                }
+            }
+            pieces.push(piece);
+        }
+        if pieces.iter().all(|piece| piece.weight == 0) {
+            return Err("every piece has a weight of 0".to_owned());
+        }
+        Ok(Self {
+            name: file.name,
+            pieces,
+        })
+    }
+
+    /// Returns the randomizer weight of each piece, in set order.
+    pub fn weights(&self) -> Vec<u32> {
+        self.pieces.iter().map(|piece| piece.weight).collect()
+    }
+}
+
+/// Loads the standard set followed by every valid set found in PIECE_SET_DIR.
+/// Files that fail to load are skipped with a message on standard error.
+pub fn load_piece_sets() -> Vec<PieceSet> {
+    let mut sets = vec![PieceSet::standard()];
+    let Ok(entries) = fs::read_dir(Path::new(PIECE_SET_DIR)) else {
+        return sets;
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| PieceSet::from_toml(&text))
+        {
+            Ok(set) => sets.push(set),
+            Err(e) => eprintln!("Skipping piece set {}: {}", path.display(), e),
+        }
+    }
+    sets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A set of one piece with extra keys for it.
+    fn one_piece(keys: &str) -> Result<PieceSet, String> {
+        PieceSet::from_toml(&format!(
+            "name = \"Test\"\n[[piece]]\nname = \"T\"\ncolor = \"#ff8000\"\nshape = [\".#.\", \"###\"]\n{}",
+            keys
+        ))
+    }
+
+    #[test]
+    fn bundled_pentominoes_load() {
+        let set = PieceSet::from_toml(include_str!("../../pieces/pentominoes.toml")).unwrap();
+        assert_eq!(set.pieces.len(), 18);
+        assert_eq!(set.pieces[0].color, Color::from_hex(0xe67e22));
+    }
+
+    #[test]
+    fn spawn_positions_must_fit_the_grid() {
+        assert!(one_piece("").is_ok());
+        assert!(one_piece("spawn_x = 0\nspawn_y = 0").is_ok());
+        // The box is 3 wide, but only the cells count
+        assert!(one_piece(&format!("spawn_x = {}", GRID_WIDTH / 2 - 3)).is_ok());
+        let err = one_piece(&format!("spawn_x = {}", GRID_WIDTH - 2)).unwrap_err();
+        assert!(err.contains("outside the grid"), "{}", err);
+        assert!(one_piece("spawn_x = -1").is_err());
+        assert!(one_piece("spawn_y = -1").is_err());
+        // Fits the normal grid, but not the half-size grid of Big mode
+        let err = one_piece(&format!("spawn_x = {}", GRID_WIDTH - 3)).unwrap_err();
+        assert!(err.contains("Big mode"), "{}", err);
+    }
+
+    #[test]
+    fn colors_must_be_hex() {
+        assert!(one_piece("").is_ok());
+        let err = PieceSet::from_toml("name = \"X\"\n[[piece]]\nname = \"A\"\ncolor = \"red\"\nshape = [\"#\"]")
+            .unwrap_err();
+        assert!(err.contains("invalid color"), "{}", err);
+    }
+}
@@ -0,0 +1,122 @@
+// This module keeps the list of local player profiles and selects the one whose files are used.
+//
+// Principles and design choices:
+//
+// - A profile is just a name: its settings, key bindings, high scores, records, statistics, daily
+//   history, puzzle progress and saved boards are the usual files, stored in the profile's own
+//   subdirectory by the storage module once the profile is activated.
+// - Loading the list has no side effect: callers activate the profile explicitly, so the files in use
+//   never change behind their back.
+// - The default profile uses the directories the game used before profiles existed, so existing files
+//   keep working as the "Default" profile without any migration.
+// - Names double as directory names, so they are limited to letters, digits, spaces, '-' and '_', and
+//   two names differing only in case are refused.
+// - The list and the profile used last are kept in a file shared by all profiles; the game starts with
+//   the last profile selected on the profile screen.
+// - Replays are not kept per profile: the game does not record replays yet. Recording one would need
+//   the inputs and the piece randomizer seed of each game; once it exists, its files belong in the
+//   profile directory through the storage module like everything else.
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+/// File name of the profile list inside the shared data directory.
+const PROFILES_FILE: &str = "profiles.toml";
+
+/// Name of the profile stored in the original data and config directories.
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Longest profile name accepted.
+pub const MAX_PROFILE_NAME: usize = 12;
+
+/// The local profiles, and the one used last.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub last: String,
+}
+
+/// Implementation of Profiles methods.
+impl Profiles {
+    /// Loads the profile list from the shared data directory. The storage module is not pointed at
+    /// the profile used last until activate() is called.
+    pub fn load() -> Self {
+        let mut profiles: Self = storage::load_in(&storage::shared_data_dir(), PROFILES_FILE);
+        profiles.names.retain(|name| valid_name(name) && name != DEFAULT_PROFILE);
+        profiles.names.insert(0, DEFAULT_PROFILE.to_owned());
+        if !profiles.names.contains(&profiles.last) {
+            profiles.last = DEFAULT_PROFILE.to_owned();
+        }
+        profiles
+    }
+
+    /// Points the storage module at the files of the profile in use.
+    pub fn activate(&self) {
+        storage::set_profile((self.last != DEFAULT_PROFILE).then_some(self.last.as_str()));
+    }
+
+    /// Saves the profile list to the shared data directory.
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_in(&storage::shared_data_dir(), PROFILES_FILE, self)
+    }
+
+    /// Returns the index of the profile in use.
+    pub fn current(&self) -> usize {
+        self.names.iter().position(|name| *name == self.last).unwrap_or(0)
+    }
+
+    /// Selects a profile: its files are used from now on, and it is the one used on the next launch.
+    ///
+    /// # Arguments
+    /// * `idx` - The index of the profile in the list
+    /// # Returns
+    /// * An error message if the profile list could not be written.
+    pub fn select(&mut self, idx: usize) -> Result<(), String> {
+        let Some(name) = self.names.get(idx) else {
+            return Err("No such profile".to_owned());
+        };
+        self.last = name.clone();
+        self.activate();
+        self.save()
+    }
+
+    /// Adds a new profile to the list and saves it.
+    ///
+    /// # Arguments
+    /// * `name` - The name typed by the player
+    /// # Returns
+    /// * The index of the new profile, or an error message if the name is refused or the list could not
+    ///   be written.
+    pub fn create(&mut self, name: &str) -> Result<usize, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Type a name first".to_owned());
+        }
+        if !valid_name(name) {
+            return Err("Use letters, digits, spaces, - and _".to_owned());
+        }
+        if self.names.iter().any(|other| other.to_lowercase() == name.to_lowercase()) {
+            return Err(format!("{} already exists", name));
+        }
+        self.names.push(name.to_owned());
+        self.save()?;
+        Ok(self.names.len() - 1)
+    }
+}
+
+/// Whether a character can be typed in a profile name.
+pub fn valid_char(c: char) -> bool {
+    c.is_alphanumeric() || c == ' ' || c == '-' || c == '_'
+}
+
+/// Whether a name can be used for a profile (and its directory).
+fn valid_name(name: &str) -> bool {
+    let trimmed = name.trim();
+    !trimmed.is_empty()
+        && trimmed == name
+        && name.chars().count() <= MAX_PROFILE_NAME
+        && name.chars().all(valid_char)
+}
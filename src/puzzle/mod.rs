@@ -0,0 +1,259 @@
+// This module defines puzzles: hand-authored starting boards with a fixed piece sequence and a goal,
+// loaded from packs of TOML files, plus the record of which puzzles the player has solved.
+//
+// Principles and design choices:
+//
+// - Puzzles always use the standard tetrominoes, referred to by their names (I, O, S, Z, J, L, T), so
+//   packs stay readable and independent from custom piece sets.
+// - Boards are written as rows of text aligned to the bottom of the grid: '.' is empty, '#' or 'X' is
+//   garbage and a piece letter is a block of that piece's color.
+// - Files are parsed into plain serde structs and validated into Puzzle values, so a broken pack
+//   produces a readable error message instead of a panic.
+// - The same board and piece formats are used by the board editor to save its boards.
+// - Progress is stored by "pack/puzzle" name in the data directory through the storage module.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{GARBAGE_COLOR, Grid};
+use crate::parameters::{GRID_HEIGHT, GRID_WIDTH};
+use crate::piece_set::PieceSet;
+use crate::storage;
+
+/// Directory scanned for puzzle pack files (relative to the working directory).
+pub const PUZZLE_DIR: &str = "puzzles";
+
+/// File name of the puzzle progress inside the data directory.
+const PROGRESS_FILE: &str = "puzzles.toml";
+
+/// Goal that solves a puzzle.
+///  - ClearAll: Leave the board completely empty.
+///  - ClearLines: Clear the given number of lines.
+///  - TSpin: Clear the given number of lines at once with a T-spin (3 for a T-spin triple).
+///  - Survive: Lock the given number of pieces without topping out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PuzzleGoal {
+    ClearAll,
+    ClearLines { lines: u32 },
+    #[serde(rename = "tspin")]
+    TSpin { lines: u32 },
+    Survive { pieces: u32 },
+}
+
+/// Implementation of PuzzleGoal methods.
+impl PuzzleGoal {
+    /// Returns a short description of the goal for the side panel.
+    pub fn description(&self) -> String {
+        match self {
+            PuzzleGoal::ClearAll => "Clear all".to_owned(),
+            PuzzleGoal::ClearLines { lines } => format!("Clear {} lines", lines),
+            PuzzleGoal::TSpin { lines: 1 } => "T-spin single".to_owned(),
+            PuzzleGoal::TSpin { lines: 2 } => "T-spin double".to_owned(),
+            PuzzleGoal::TSpin { lines: 3 } => "T-spin triple".to_owned(),
+            PuzzleGoal::TSpin { lines } => format!("T-spin {} lines", lines),
+            PuzzleGoal::Survive { pieces } => format!("Survive {}", pieces),
+        }
+    }
+}
+
+/// A single puzzle, validated and ready to play.
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    /// Unique "pack/puzzle" name, used for progress tracking.
+    pub id: String,
+    pub grid: Grid,
+    /// Piece indices into the standard piece set.
+    pub sequence: Vec<usize>,
+    pub hold: Option<usize>,
+    pub goal: PuzzleGoal,
+}
+
+/// Raw puzzle pack file as written on disk.
+#[derive(Deserialize)]
+struct PuzzlePackFile {
+    name: String,
+    puzzle: Vec<PuzzleEntry>,
+}
+
+/// Raw puzzle entry as written on disk.
+#[derive(Deserialize)]
+struct PuzzleEntry {
+    name: String,
+    #[serde(default)]
+    board: Vec<String>,
+    pieces: String,
+    hold: Option<String>,
+    goal: PuzzleGoal,
+}
+
+/// Names of the puzzles the player has solved.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PuzzleProgress {
+    #[serde(default)]
+    pub solved: Vec<String>,
+}
+
+/// Implementation of PuzzleProgress methods.
+impl PuzzleProgress {
+    /// Loads the puzzle progress from the data directory.
+    pub fn load() -> Self {
+        storage::load(PROGRESS_FILE)
+    }
+
+    /// Whether the puzzle with the given id has been solved.
+    pub fn is_solved(&self, id: &str) -> bool {
+        self.solved.iter().any(|s| s == id)
+    }
+
+    /// Marks a puzzle as solved and saves the progress if it was not solved before.
+    ///
+    /// # Returns
+    /// * An error message if the progress could not be saved.
+    pub fn mark_solved(&mut self, id: &str) -> Result<(), String> {
+        if self.is_solved(id) {
+            return Ok(());
+        }
+        self.solved.push(id.to_owned());
+        storage::save(PROGRESS_FILE, self)
+    }
+}
+
+/// Parses and validates a puzzle pack from TOML text.
+///
+/// # Arguments
+/// * `text` - The contents of a puzzle pack file
+/// # Returns
+/// * The puzzles of the pack, or an error message describing the first problem found.
+pub fn parse_puzzle_pack(text: &str) -> Result<Vec<Puzzle>, String> {
+    let file: PuzzlePackFile = toml::from_str(text).map_err(|e| e.to_string())?;
+    let standard = PieceSet::standard();
+    file.puzzle
+        .into_iter()
+        .map(|entry| {
+            let context = |e: String| format!("puzzle {}: {}", entry.name, e);
+            let grid = parse_board(&entry.board, &standard).map_err(context)?;
+            let sequence = parse_pieces(&entry.pieces).map_err(context)?;
+            if sequence.is_empty() {
+                return Err(context("no pieces".to_owned()));
+            }
+            let hold = match entry.hold.as_deref().map(str::trim) {
+                None | Some("") => None,
+                Some(name) => Some(
+                    name.chars()
+                        .next()
+                        .and_then(|c| piece_index(&standard, c))
+                        .ok_or_else(|| context(format!("unknown hold piece {:?}", name)))?,
+                ),
+            };
+            Ok(Puzzle {
+                id: format!("{}/{}", file.name, entry.name),
+                grid,
+                sequence,
+                hold,
+                goal: entry.goal,
+            })
+        })
+        .collect()
+}
+
+/// Loads every valid puzzle pack found in PUZZLE_DIR, in file name order.
+/// Packs that fail to load are skipped with a message on standard error.
+pub fn load_puzzles() -> Vec<Puzzle> {
+    let Ok(entries) = fs::read_dir(Path::new(PUZZLE_DIR)) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+    let mut puzzles = Vec::new();
+    for path in paths {
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_puzzle_pack(&text))
+        {
+            Ok(pack) => puzzles.extend(pack),
+            Err(e) => eprintln!("Skipping puzzle pack {}: {}", path.display(), e),
+        }
+    }
+    puzzles
+}
+
+/// Parses a piece sequence written as standard piece letters (e.g. "TIOL"), ignoring whitespace.
+///
+/// # Arguments
+/// * `text` - The piece letters
+/// # Returns
+/// * The piece indices into the standard piece set, or an error message for an unknown letter.
+pub fn parse_pieces(text: &str) -> Result<Vec<usize>, String> {
+    let standard = PieceSet::standard();
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| piece_index(&standard, c).ok_or_else(|| format!("unknown piece {:?}", c)))
+        .collect()
+}
+
+/// Writes a grid as board rows in the puzzle format, from the highest non-empty row to the bottom.
+/// Blocks are written as the letter of the standard piece with the same color, or '#' otherwise.
+///
+/// # Arguments
+/// * `grid` - The grid to write
+pub fn board_rows(grid: &Grid) -> Vec<String> {
+    let standard = PieceSet::standard();
+    let top = grid
+        .iter()
+        .position(|row| row.iter().any(|cell| cell.is_some()))
+        .unwrap_or(GRID_HEIGHT);
+    grid[top..]
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| match cell {
+                    None => '.',
+                    Some(color) => standard
+                        .pieces
+                        .iter()
+                        .find(|piece| piece.color == *color)
+                        .and_then(|piece| piece.name.chars().next())
+                        .unwrap_or('#'),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the index of the standard piece with the given single-letter name.
+pub fn piece_index(standard: &PieceSet, name: char) -> Option<usize> {
+    let name = name.to_ascii_uppercase().to_string();
+    standard.pieces.iter().position(|piece| piece.name == name)
+}
+
+/// Parses a board written as rows of text, aligned to the bottom of the grid.
+pub fn parse_board(rows: &[String], standard: &PieceSet) -> Result<Grid, String> {
+    if rows.len() > GRID_HEIGHT {
+        return Err(format!("board has {} rows, at most {} fit", rows.len(), GRID_HEIGHT));
+    }
+    let mut grid: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
+    let top = GRID_HEIGHT - rows.len();
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() > GRID_WIDTH {
+            return Err(format!("board row {:?} is wider than {} cells", row, GRID_WIDTH));
+        }
+        for (x, c) in row.chars().enumerate() {
+            grid[top + y][x] = match c {
+                '.' | ' ' => None,
+                '#' | 'X' => Some(GARBAGE_COLOR),
+                _ => {
+                    let idx = piece_index(standard, c)
+                        .ok_or_else(|| format!("unknown board cell {:?}", c))?;
+                    Some(standard.pieces[idx].color)
+                }
+            };
+        }
+    }
+    Ok(grid)
+}
@@ -0,0 +1,140 @@
+// This module keeps the player's personal records between runs.
+//
+// Principles and design choices:
+//
+// - Sprint personal bests are stored per line goal, with the split time of every 10 lines, so a new run
+//   can be compared split by split against the best one.
+// - Records are plain serde structs persisted through the storage module (atomic TOML writes).
+// - Only improvements replace a stored record; submit() reports whether the run was a new best.
+// - Statistics add up every finished game (games, lines and play time) and live in the data
+//   directory like the records, so each profile keeps its own. A game resumed from its end (rewind or
+//   undo in practice and puzzles) is taken back out, so it counts once when it ends again.
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_info::GameInfo;
+use crate::storage;
+
+/// File name of the sprint records inside the data directory.
+const SPRINT_FILE: &str = "sprint.toml";
+
+/// File name of the statistics inside the data directory.
+const STATISTICS_FILE: &str = "statistics.toml";
+
+/// Best sprint run for one line goal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SprintBest {
+    pub lines: u32,
+    pub time: f64,
+    pub splits: Vec<f64>,
+}
+
+/// Sprint personal bests for every line goal played so far.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SprintRecords {
+    #[serde(default)]
+    pub best: Vec<SprintBest>,
+}
+
+/// Implementation of SprintRecords methods.
+impl SprintRecords {
+    /// Loads the sprint records from the data directory.
+    pub fn load() -> Self {
+        storage::load(SPRINT_FILE)
+    }
+
+    /// Saves the sprint records to the data directory.
+    pub fn save(&self) -> Result<(), String> {
+        storage::save(SPRINT_FILE, self)
+    }
+
+    /// Returns the personal best for the given line goal, if any.
+    pub fn best(&self, lines: u32) -> Option<&SprintBest> {
+        self.best.iter().find(|b| b.lines == lines)
+    }
+
+    /// Records a finished run, replacing the personal best if it is faster.
+    ///
+    /// # Arguments
+    /// * `lines` - The line goal of the run
+    /// * `time` - The final time, in seconds
+    /// * `splits` - The split time of every 10 lines, in seconds
+    /// # Returns
+    /// * `true` if the run is a new personal best, `false` otherwise.
+    pub fn submit(&mut self, lines: u32, time: f64, splits: &[f64]) -> bool {
+        let run = SprintBest {
+            lines,
+            time,
+            splits: splits.to_vec(),
+        };
+        match self.best.iter_mut().find(|b| b.lines == lines) {
+            Some(best) if best.time <= time => false,
+            Some(best) => {
+                *best = run;
+                true
+            }
+            None => {
+                self.best.push(run);
+                true
+            }
+        }
+    }
+}
+
+/// Totals over every finished game.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Statistics {
+    #[serde(default)]
+    pub games: u32,
+    #[serde(default)]
+    pub lines: u32,
+    /// Play time, in seconds.
+    #[serde(default)]
+    pub time: f64,
+    /// Lines and time of the last game recorded in this run, until it is taken back.
+    #[serde(skip)]
+    last: Option<(u32, f64)>,
+}
+
+/// Implementation of Statistics methods.
+impl Statistics {
+    /// Loads the statistics from the data directory.
+    pub fn load() -> Self {
+        storage::load(STATISTICS_FILE)
+    }
+
+    /// Adds a finished game to the totals and saves them.
+    ///
+    /// # Arguments
+    /// * `info` - The game info at the end of the game
+    /// # Returns
+    /// * An error message if the statistics could not be saved.
+    pub fn record(&mut self, info: &GameInfo) -> Result<(), String> {
+        self.games += 1;
+        self.lines += info.lines_cleared;
+        self.time += info.time;
+        self.last = Some((info.lines_cleared, info.time));
+        storage::save(STATISTICS_FILE, self)
+    }
+
+    /// Takes the last recorded game back out of the totals, when a game that ended is resumed (by
+    /// rewind or undo), so it is only counted once, when it ends for good.
+    ///
+    /// # Returns
+    /// * An error message if the statistics could not be saved.
+    pub fn take_back_last(&mut self) -> Result<(), String> {
+        let Some((lines, time)) = self.last.take() else {
+            return Ok(());
+        };
+        self.games = self.games.saturating_sub(1);
+        self.lines = self.lines.saturating_sub(lines);
+        self.time = (self.time - time).max(0.0);
+        storage::save(STATISTICS_FILE, self)
+    }
+
+    /// Returns the totals as one line of text, e.g. "12 games  340 lines  1h05 played".
+    pub fn summary(&self) -> String {
+        let minutes = (self.time / 60.0) as u32;
+        format!("{} games  {} lines  {}h{:02} played", self.games, self.lines, minutes / 60, minutes % 60)
+    }
+}
@@ -0,0 +1,395 @@
+// This module defines the player's settings: handling, visuals and gameplay options that used to be
+// compile-time constants, loaded from a TOML file in the user config directory and edited in game.
+//
+// Principles and design choices:
+//
+// - The file is parsed into plain serde structs whose values are all optional, then validated into a
+//   Settings value; a missing value takes its default and an invalid one (out of range, bad color) is
+//   reported and replaced by its default, so a broken file never stops the game from starting.
+// - The settings in effect are kept in one process-wide value read through current(), so drawing and
+//   rule code deep in the engine (fall speed, grid colors) picks up changes without threading the
+//   settings through every call. The main loop replaces it with apply() when the player edits them.
+// - Custom fall delays only apply to unranked games (see GameMode::is_ranked()): high score tables
+//   are keyed by difficulty and the daily challenge is compared between players, so ranked games keep
+//   the built-in speeds.
+// - The block size sets the window size, which macroquad fixes when the window opens, so it only takes
+//   effect on the next launch.
+// - The settings screen edits values through options()/change_option(), like the mode options of the
+//   start menu; every edit stays within the validated ranges.
+// - The file is saved with the storage module's atomic writes.
+// - Out of scope: there is no audio section because the game plays no sound yet. Piece colors are not
+//   settings either: they identify pieces in saved boards and puzzles (the editor maps a block back to
+//   its brush by color), so they stay the tetromino COLORS and the colors of custom piece set files.
+
+use std::fs;
+use std::sync::RwLock;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::parameters::{
+    BLOCK_SIZE, Difficulty, SOFT_DROP_DELAY_HORIZONTAL, SOFT_DROP_DELAY_VERTICAL,
+};
+use crate::storage;
+
+/// File name of the settings inside the config directory.
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// Accepted range of the handling repeat delays, in seconds.
+const REPEAT_RANGE: (f32, f32) = (0.01, 0.5);
+
+/// Accepted range of the block size, in pixels.
+const BLOCK_SIZE_RANGE: (u32, u32) = (24, 40);
+
+/// Accepted range of the fall delays, in seconds per row.
+const FALL_DELAY_RANGE: (f32, f32) = (0.05, 2.0);
+
+/// Colors offered on the settings screen for the background and the grid lines.
+const PALETTE: [(&str, Color); 8] = [
+    ("Black", BLACK),
+    ("Navy", Color::from_hex(0x0a1028)),
+    ("Dark purple", DARKPURPLE),
+    ("Dark green", DARKGREEN),
+    ("Dark gray", DARKGRAY),
+    ("Gray", GRAY),
+    ("Light gray", LIGHTGRAY),
+    ("White", WHITE),
+];
+
+/// Validated settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    /// Delay between repeated left/right moves while the key is held, in seconds.
+    pub move_repeat: f32,
+    /// Delay between repeated soft drop steps while the key is held, in seconds.
+    pub soft_drop_repeat: f32,
+    /// Size of a block in pixels (applied on the next launch).
+    pub block_size: u32,
+    pub show_grid: bool,
+    pub background: Color,
+    pub grid_color: Color,
+    /// Fall delay of each difficulty in unranked games, in seconds per row (Easy, Medium, Hard).
+    pub fall_delays: [f32; 3],
+    /// Whether a finesse fault flashes a warning on the board.
+    pub finesse_warning: bool,
+    /// Whether practice and puzzles can be rewound.
+    pub rewind: bool,
+}
+
+/// Settings in effect.
+static CURRENT: RwLock<Settings> = RwLock::new(Settings::DEFAULT);
+
+/// Returns the settings in effect.
+pub fn current() -> Settings {
+    *CURRENT.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Puts the given settings in effect.
+pub fn apply(settings: Settings) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = settings;
+}
+
+/// Implementation of Settings methods.
+impl Settings {
+    /// The settings used when the file is missing, matching the former constants.
+    pub const DEFAULT: Settings = Settings {
+        move_repeat: SOFT_DROP_DELAY_HORIZONTAL,
+        soft_drop_repeat: SOFT_DROP_DELAY_VERTICAL,
+        block_size: BLOCK_SIZE as u32,
+        show_grid: true,
+        background: BLACK,
+        grid_color: GRAY,
+        fall_delays: [
+            Difficulty::Easy.fall_delay(),
+            Difficulty::Medium.fall_delay(),
+            Difficulty::Hard.fall_delay(),
+        ],
+        finesse_warning: true,
+        rewind: true,
+    };
+
+    /// Loads the settings from the config directory.
+    ///
+    /// # Returns
+    /// * The settings, and a message for every problem found; invalid values keep their default.
+    pub fn load() -> (Self, Vec<String>) {
+        let Ok(text) = fs::read_to_string(storage::config_dir().join(SETTINGS_FILE)) else {
+            return (Self::DEFAULT, Vec::new());
+        };
+        Self::parse(&text)
+    }
+
+    /// Parses and validates settings from TOML text.
+    ///
+    /// # Arguments
+    /// * `text` - The contents of a settings file
+    /// # Returns
+    /// * The settings, and a message for every problem found; invalid values keep their default.
+    pub fn parse(text: &str) -> (Self, Vec<String>) {
+        let file: SettingsFile = match toml::from_str(text) {
+            Ok(file) => file,
+            Err(e) => return (Self::DEFAULT, vec![e.message().to_owned()]),
+        };
+        let mut errors = Vec::new();
+        let default = Self::DEFAULT;
+        let handling = file.handling;
+        let visuals = file.visuals;
+        let gameplay = file.gameplay;
+        let settings = Self {
+            move_repeat: checked(
+                "handling.move_repeat",
+                handling.move_repeat,
+                REPEAT_RANGE,
+                default.move_repeat,
+                &mut errors,
+            ),
+            soft_drop_repeat: checked(
+                "handling.soft_drop_repeat",
+                handling.soft_drop_repeat,
+                REPEAT_RANGE,
+                default.soft_drop_repeat,
+                &mut errors,
+            ),
+            block_size: checked(
+                "visuals.block_size",
+                visuals.block_size,
+                BLOCK_SIZE_RANGE,
+                default.block_size,
+                &mut errors,
+            ),
+            show_grid: visuals.show_grid.unwrap_or(default.show_grid),
+            background: checked_color(
+                "visuals.background",
+                visuals.background,
+                default.background,
+                &mut errors,
+            ),
+            grid_color: checked_color(
+                "visuals.grid_color",
+                visuals.grid_color,
+                default.grid_color,
+                &mut errors,
+            ),
+            fall_delays: [
+                checked(
+                    "gameplay.fall_delay_easy",
+                    gameplay.fall_delay_easy,
+                    FALL_DELAY_RANGE,
+                    default.fall_delays[0],
+                    &mut errors,
+                ),
+                checked(
+                    "gameplay.fall_delay_medium",
+                    gameplay.fall_delay_medium,
+                    FALL_DELAY_RANGE,
+                    default.fall_delays[1],
+                    &mut errors,
+                ),
+                checked(
+                    "gameplay.fall_delay_hard",
+                    gameplay.fall_delay_hard,
+                    FALL_DELAY_RANGE,
+                    default.fall_delays[2],
+                    &mut errors,
+                ),
+            ],
+            finesse_warning: gameplay.finesse_warning.unwrap_or(default.finesse_warning),
+            rewind: gameplay.rewind.unwrap_or(default.rewind),
+        };
+        (settings, errors)
+    }
+
+    /// Saves the settings to the config directory.
+    pub fn save(&self) -> Result<(), String> {
+        let file = SettingsFile {
+            handling: HandlingSection {
+                move_repeat: Some(self.move_repeat),
+                soft_drop_repeat: Some(self.soft_drop_repeat),
+            },
+            visuals: VisualsSection {
+                block_size: Some(self.block_size),
+                show_grid: Some(self.show_grid),
+                background: Some(color_hex(self.background)),
+                grid_color: Some(color_hex(self.grid_color)),
+            },
+            gameplay: GameplaySection {
+                fall_delay_easy: Some(self.fall_delays[0]),
+                fall_delay_medium: Some(self.fall_delays[1]),
+                fall_delay_hard: Some(self.fall_delays[2]),
+                finesse_warning: Some(self.finesse_warning),
+                rewind: Some(self.rewind),
+            },
+        };
+        storage::save_in(&storage::config_dir(), SETTINGS_FILE, &file)
+    }
+
+    /// Returns the fall delay of a difficulty, in seconds per row.
+    pub fn fall_delay(&self, difficulty: Difficulty) -> f32 {
+        let idx = Difficulty::ALL
+            .iter()
+            .position(|d| *d == difficulty)
+            .unwrap_or(0);
+        self.fall_delays[idx]
+    }
+
+    /// Returns the label and current value of each setting, for the settings screen.
+    pub fn options(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Move repeat", format!("{:.2}s", self.move_repeat)),
+            ("Soft drop repeat", format!("{:.2}s", self.soft_drop_repeat)),
+            ("Block size", format!("{}px (restart)", self.block_size)),
+            ("Grid lines", on_off(self.show_grid)),
+            ("Background", color_name(self.background)),
+            ("Grid color", color_name(self.grid_color)),
+            ("Fall Easy", format!("{:.2}s", self.fall_delays[0])),
+            ("Fall Medium", format!("{:.2}s", self.fall_delays[1])),
+            ("Fall Hard", format!("{:.2}s", self.fall_delays[2])),
+            ("Finesse warning", on_off(self.finesse_warning)),
+            ("Rewind", on_off(self.rewind)),
+        ]
+    }
+
+    /// Changes the setting at `index` forwards (1) or backwards (-1), within its valid range.
+    pub fn change_option(&mut self, index: usize, step: i32) {
+        let step_by = |value: f32, size: f32, (min, max): (f32, f32)| {
+            (((value / size).round() + step as f32) * size).clamp(min, max)
+        };
+        match index {
+            0 => self.move_repeat = step_by(self.move_repeat, 0.01, REPEAT_RANGE),
+            1 => self.soft_drop_repeat = step_by(self.soft_drop_repeat, 0.01, REPEAT_RANGE),
+            2 => {
+                let size = self.block_size as i32 + step * 2;
+                self.block_size =
+                    (size.max(0) as u32).clamp(BLOCK_SIZE_RANGE.0, BLOCK_SIZE_RANGE.1);
+            }
+            3 => self.show_grid = !self.show_grid,
+            4 => self.background = cycle_color(self.background, step),
+            5 => self.grid_color = cycle_color(self.grid_color, step),
+            6..=8 => {
+                let delay = &mut self.fall_delays[index - 6];
+                *delay = step_by(*delay, 0.05, FALL_DELAY_RANGE);
+            }
+            9 => self.finesse_warning = !self.finesse_warning,
+            10 => self.rewind = !self.rewind,
+            _ => {}
+        }
+    }
+}
+
+/// Implementation of Default for Settings.
+impl Default for Settings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Raw settings file as written on disk.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SettingsFile {
+    handling: HandlingSection,
+    visuals: VisualsSection,
+    gameplay: GameplaySection,
+}
+
+/// Raw [handling] section.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct HandlingSection {
+    move_repeat: Option<f32>,
+    soft_drop_repeat: Option<f32>,
+}
+
+/// Raw [visuals] section.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct VisualsSection {
+    block_size: Option<u32>,
+    show_grid: Option<bool>,
+    background: Option<String>,
+    grid_color: Option<String>,
+}
+
+/// Raw [gameplay] section.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct GameplaySection {
+    fall_delay_easy: Option<f32>,
+    fall_delay_medium: Option<f32>,
+    fall_delay_hard: Option<f32>,
+    finesse_warning: Option<bool>,
+    rewind: Option<bool>,
+}
+
+/// Returns a value if it lies within its range, or reports it and returns the default.
+fn checked<T: PartialOrd + Copy + std::fmt::Display>(
+    key: &str,
+    value: Option<T>,
+    (min, max): (T, T),
+    default: T,
+    errors: &mut Vec<String>,
+) -> T {
+    match value {
+        Some(value) if value >= min && value <= max => value,
+        Some(value) => {
+            errors.push(format!("{} = {} is outside {}..{}", key, value, min, max));
+            default
+        }
+        None => default,
+    }
+}
+
+/// Returns a "#rrggbb" color, or reports it and returns the default.
+fn checked_color(
+    key: &str,
+    value: Option<String>,
+    default: Color,
+    errors: &mut Vec<String>,
+) -> Color {
+    let Some(text) = value else {
+        return default;
+    };
+    match parse_color(&text) {
+        Some(color) => color,
+        None => {
+            errors.push(format!("{} = {:?} is not a #rrggbb color", key, text));
+            default
+        }
+    }
+}
+
+/// Parses a "#rrggbb" hex color, as written in the settings and piece set files.
+pub fn parse_color(text: &str) -> Option<Color> {
+    text.strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .map(Color::from_hex)
+}
+
+/// Returns a color as "#rrggbb".
+fn color_hex(color: Color) -> String {
+    let [r, g, b, _]: [u8; 4] = color.into();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Returns the palette name of a color, or its "#rrggbb" value.
+fn color_name(color: Color) -> String {
+    PALETTE
+        .iter()
+        .find(|(_, c)| color_hex(*c) == color_hex(color))
+        .map_or_else(|| color_hex(color), |(name, _)| (*name).to_owned())
+}
+
+/// Moves a color to the next (or previous) palette color; colors outside the palette start from black.
+fn cycle_color(color: Color, step: i32) -> Color {
+    let idx = PALETTE
+        .iter()
+        .position(|(_, c)| color_hex(*c) == color_hex(color))
+        .unwrap_or(0) as i32;
+    PALETTE[(idx + step).rem_euclid(PALETTE.len() as i32) as usize].1
+}
+
+/// Returns "On" or "Off" for a boolean setting.
+fn on_off(value: bool) -> String {
+    if value { "On" } else { "Off" }.to_owned()
+}
@@ -2,17 +2,37 @@
 //
 // Principles and design choices:
 //
-// - The GameState enum represents all possible states of the game: Waiting (menu), Running (active play), Paused, and GameOver.
+// - The GameState enum represents all possible states of the game: Profiles (choosing who plays, shown on launch), Waiting (menu), PuzzleSelect (puzzle list), OpenerSelect (opener list), Editor (board editor), Running (active play), Paused, NameEntry (naming a new high score), GameOver, HighScores (the high score tables), Settings (the settings screen) and Controls (the key bindings screen).
+// - NameEntry and GameOver carry an Outcome, so the game-over screen can tell a topped-out stack from a completed goal.
 // - Provides convenience methods for checking the current state, improving code readability and maintainability.
 // - Designed for clear and robust state management throughout the game loop.
 
+/// Enum representing how a game ended.
+///  - ToppedOut: The stack reached the top of the grid.
+///  - Completed: The mode's goal was reached (e.g. all Sprint lines cleared).
+///  - Failed: A puzzle's piece sequence ran out before its goal was reached.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Outcome {
+    ToppedOut,
+    Completed,
+    Failed,
+}
+
 /// Enum representing the different states of the game.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum GameState {
+    Profiles,
     Waiting,
+    PuzzleSelect,
+    OpenerSelect,
+    Editor,
     Running,
     Paused,
-    GameOver,
+    NameEntry(Outcome),
+    GameOver(Outcome),
+    HighScores,
+    Settings,
+    Controls,
 }
 
 /// Implementation of GameState methods.
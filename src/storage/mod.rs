@@ -0,0 +1,128 @@
+// This module provides persistence helpers for the small data files the game keeps between runs
+// (personal bests, records and similar).
+//
+// Principles and design choices:
+//
+// - All files live in a single per-user data directory, so the rest of the game only deals with file names;
+//   the settings file lives in the per-user config directory instead.
+// - Each player profile other than the default one gets its own subdirectory (profiles/<name>) in both
+//   directories; the selected profile is kept here, so every module saving through data_dir() and
+//   config_dir() keeps its files apart per profile without knowing about profiles.
+// - Files are TOML documents read and written through serde, keeping them human-readable.
+// - Writes are atomic: data is written to a temporary file which then replaces the original, so a crash
+//   mid-write can never leave a truncated file behind.
+// - A missing file simply yields the default value; an unreadable one is reported and also falls back to
+//   the default, so bad data never prevents the game from starting.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Name of the application directory inside the user data directory.
+const APP_DIR: &str = "tetris-rs";
+
+/// Name of the directory holding the profile subdirectories.
+const PROFILES_DIR: &str = "profiles";
+
+/// Subdirectory of the selected profile, or `None` for the default profile.
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Selects the profile whose files are read and written from now on.
+///
+/// # Arguments
+/// * `subdir` - The profile's subdirectory name, or `None` for the default profile
+pub fn set_profile(subdir: Option<&str>) {
+    if let Ok(mut profile) = PROFILE.write() {
+        *profile = subdir.map(str::to_owned);
+    }
+}
+
+/// Returns the subdirectory of the selected profile inside a base directory.
+fn in_profile(dir: PathBuf) -> PathBuf {
+    match PROFILE.read().ok().and_then(|profile| profile.clone()) {
+        Some(subdir) => dir.join(PROFILES_DIR).join(subdir),
+        None => dir,
+    }
+}
+
+/// Returns the data directory shared by all profiles, falling back to the working directory.
+pub fn shared_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Returns the directory where the data files of the selected profile are stored.
+pub fn data_dir() -> PathBuf {
+    in_profile(shared_data_dir())
+}
+
+/// Returns the directory where the settings files of the selected profile are stored, falling back to
+/// the working directory.
+pub fn config_dir() -> PathBuf {
+    in_profile(
+        dirs::config_dir()
+            .map(|dir| dir.join(APP_DIR))
+            .unwrap_or_else(|| PathBuf::from(".")),
+    )
+}
+
+/// Loads a data file of the selected profile, returning the default value if it is missing or invalid.
+///
+/// # Arguments
+/// * `name` - The file name inside the data directory
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    load_in(&data_dir(), name)
+}
+
+/// Loads a file from the given directory, returning the default value if it is missing or invalid.
+///
+/// # Arguments
+/// * `dir` - The directory to load from
+/// * `name` - The file name inside the directory
+pub fn load_in<T: DeserializeOwned + Default>(dir: &Path, name: &str) -> T {
+    let path = dir.join(name);
+    let Ok(text) = fs::read_to_string(&path) else {
+        return T::default();
+    };
+    toml::from_str(&text).unwrap_or_else(|e| {
+        eprintln!("Ignoring invalid data file {}: {}", path.display(), e);
+        T::default()
+    })
+}
+
+/// Saves a data file of the selected profile atomically.
+///
+/// # Arguments
+/// * `name` - The file name inside the data directory
+/// * `value` - The value to serialize
+/// # Returns
+/// * An error message if the file could not be written.
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<(), String> {
+    save_in(&data_dir(), name, value)
+}
+
+/// Saves a file atomically in the given directory.
+///
+/// # Arguments
+/// * `dir` - The directory to save in, created if missing
+/// * `name` - The file name inside the directory
+/// * `value` - The value to serialize
+/// # Returns
+/// * An error message if the file could not be written.
+pub fn save_in<T: Serialize>(dir: &Path, name: &str, value: &T) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let text = toml::to_string(value).map_err(|e| e.to_string())?;
+
+    // Write to a temporary file first, then rename it over the original
+    let path = dir.join(name);
+    let tmp = dir.join(format!("{}.tmp", name));
+    let mut file = File::create(&tmp).map_err(|e| e.to_string())?;
+    file.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())?;
+    fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
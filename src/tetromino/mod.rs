@@ -2,24 +2,102 @@
 //
 // Principles and design choices:
 //
-// - TetrominoShape is defined as a 4x4 grid ([[u8; 4]; 4]) because all standard Tetris pieces (I, O, S, Z, J, L, T)
-//   can fit within a 4x4 matrix in any rotation. This uniform size simplifies rotation, collision detection, and drawing logic.
-// - The SHAPES constant lists all 7 standard tetrominoes using this 4x4 representation.
-// - The rotate() function rotates a tetromino 90 degrees clockwise within its 4x4 grid. This is done by transposing
-//   and then reversing rows, which is efficient and works for all pieces due to the fixed size.
-// - draw_tetromino() draws any tetromino on the grid by iterating over its 4x4 matrix and drawing a block for each nonzero cell.
+// - TetrominoShape is a square matrix of up to MAX_SHAPE_SIZE x MAX_SHAPE_SIZE cells together with its actual size.
+//   All standard Tetris pieces (I, O, S, Z, J, L, T) use a 4x4 matrix, while pentominoes and other data-defined
+//   polyominoes (see the piece_set module) can use up to 5x5. Indexing works like a plain 2D array (shape[row][col]).
+// - The SHAPES constant lists all 7 standard tetrominoes using the 4x4 representation.
+// - The rotate() function rotates a shape 90 degrees clockwise within its own square matrix. This is done by transposing
+//   and then reversing rows, which is efficient and keeps the standard tetrominoes rotating exactly as before.
+// - draw_tetromino() draws any shape on the grid by iterating over its matrix and drawing a block for each nonzero cell.
+//   A scale factor lets each block cover scale x scale grid cells for Big mode.
 // - TetrominoBag implements the "bag of 7" system: each cycle, all 7 tetrominoes are shuffled and dealt out one by one,
-//   ensuring fair and modern piece distribution (no repeats until all have appeared).
+//   ensuring fair and modern piece distribution (no repeats until all have appeared). Per-piece weights generalize it:
+//   a piece with weight N appears N times in each bag, and weight 0 removes it.
+// - NesRandomizer reproduces the classic NES randomizer: a uniform roll that is rerolled once when it
+//   repeats the previous piece, so repeats are rarer but droughts are possible.
+// - PieceQueue is the engine's source of upcoming pieces: either a bag, or a fixed sequence (used by puzzles)
+//   that eventually runs out.
 //
 // This approach is standard in modern Tetris implementations for simplicity, fairness, and code maintainability.
 
+use std::ops::Index;
+
 use macroquad::prelude::*;
-use macroquad::rand::ChooseRandom;
+use macroquad::rand::{ChooseRandom, RandGenerator, gen_range};
+
+use crate::game::block_cells;
+use crate::parameters::block_size;
+
+/// Largest supported shape matrix size (pentominoes need a 5x5 bounding box).
+pub const MAX_SHAPE_SIZE: usize = 5;
+
+/// A piece shape represented as a square grid of `size` x `size` cells.
+/// Cells outside the used size are always 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TetrominoShape {
+    cells: [[u8; MAX_SHAPE_SIZE]; MAX_SHAPE_SIZE],
+    size: usize,
+}
+
+/// Implementation of TetrominoShape methods.
+impl TetrominoShape {
+    /// Builds a shape from a standard 4x4 tetromino matrix.
+    pub const fn from_4x4(rows: [[u8; 4]; 4]) -> Self {
+        let mut cells = [[0u8; MAX_SHAPE_SIZE]; MAX_SHAPE_SIZE];
+        let mut row = 0;
+        while row < 4 {
+            let mut col = 0;
+            while col < 4 {
+                cells[row][col] = rows[row][col];
+                col += 1;
+            }
+            row += 1;
+        }
+        Self { cells, size: 4 }
+    }
+
+    /// Builds a shape from rows of cells, padding it to a square matrix.
+    ///
+    /// # Arguments
+    /// * `rows` - The rows of the shape; nonzero values are filled cells
+    /// # Returns
+    /// * The shape, or an error message if it is empty or larger than MAX_SHAPE_SIZE.
+    pub fn from_rows(rows: &[Vec<u8>]) -> Result<Self, String> {
+        let size = rows
+            .len()
+            .max(rows.iter().map(|row| row.len()).max().unwrap_or(0));
+        if size > MAX_SHAPE_SIZE {
+            return Err(format!(
+                "shape is {}x{} but at most {}x{} is supported",
+                size, size, MAX_SHAPE_SIZE, MAX_SHAPE_SIZE
+            ));
+        }
+        let mut cells = [[0u8; MAX_SHAPE_SIZE]; MAX_SHAPE_SIZE];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                cells[y][x] = u8::from(cell != 0);
+            }
+        }
+        if cells.iter().all(|row| row.iter().all(|&c| c == 0)) {
+            return Err("shape has no filled cells".to_owned());
+        }
+        Ok(Self { cells, size })
+    }
 
-use crate::parameters::BLOCK_SIZE;
+    /// Returns the side length of the shape's square matrix.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
 
-/// Type alias for a Tetromino shape represented as a 4x4 grid
-pub type TetrominoShape = [[u8; 4]; 4];
+/// Allows shapes to be indexed like a 2D array: `shape[row][col]`.
+impl Index<usize> for TetrominoShape {
+    type Output = [u8; MAX_SHAPE_SIZE];
+
+    fn index(&self, row: usize) -> &Self::Output {
+        &self.cells[row]
+    }
+}
 
 /// All 7 standard Tetromino shapes in their initial orientation
 /// Each shape is a 4x4 grid where 1s represent filled blocks and 0s represent empty space.
@@ -33,30 +111,37 @@ pub type TetrominoShape = [[u8; 4]; 4];
 /// T: T Shape
 pub const SHAPES: [TetrominoShape; 7] = [
     // I
-    [[0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0], [0, 0, 0, 0]],
+    TetrominoShape::from_4x4([[0, 0, 0, 0], [1, 1, 1, 1], [0, 0, 0, 0], [0, 0, 0, 0]]),
     // O
-    [[0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+    TetrominoShape::from_4x4([[0, 1, 1, 0], [0, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]]),
     // S
-    [[0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+    TetrominoShape::from_4x4([[0, 1, 1, 0], [1, 1, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]]),
     // Z
-    [[1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+    TetrominoShape::from_4x4([[1, 1, 0, 0], [0, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]]),
     // J
-    [[1, 0, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+    TetrominoShape::from_4x4([[1, 0, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]]),
     // L
-    [[0, 0, 1, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+    TetrominoShape::from_4x4([[0, 0, 1, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]]),
     // T
-    [[0, 1, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]],
+    TetrominoShape::from_4x4([[0, 1, 0, 0], [1, 1, 1, 0], [0, 0, 0, 0], [0, 0, 0, 0]]),
 ];
 
+/// Colors of the 7 standard tetrominoes, in the same order as SHAPES.
+pub const COLORS: [Color; 7] = [ORANGE, YELLOW, GREEN, RED, BLUE, PURPLE, PINK];
+
 /// Rotate the tetromino
 ///
 /// # Arguments
 /// * `shape` - The TetrominoShape to rotate
 pub fn rotate(shape: &TetrominoShape) -> TetrominoShape {
-    let mut new_shape = [[0u8; 4]; 4];
-    for y in 0..4 {
-        for x in 0..4 {
-            new_shape[x][3 - y] = shape[y][x];
+    let size = shape.size;
+    let mut new_shape = TetrominoShape {
+        cells: [[0u8; MAX_SHAPE_SIZE]; MAX_SHAPE_SIZE],
+        size,
+    };
+    for y in 0..size {
+        for x in 0..size {
+            new_shape.cells[x][size - 1 - y] = shape.cells[y][x];
         }
     }
     new_shape
@@ -69,34 +154,83 @@ pub fn rotate(shape: &TetrominoShape) -> TetrominoShape {
 /// * `grid_x` - The x position on the grid to draw at (in grid coordinates)
 /// * `grid_y` - The y position on the grid to draw at (in grid coordinates)
 /// * `color` - The color to use for drawing the tetromino
-pub fn draw_tetromino(shape: &TetrominoShape, grid_x: i32, grid_y: i32, color: Color) {
-    for row in 0..4 {
-        for col in 0..4 {
+/// * `scale` - The number of grid cells covered by each block along each axis
+pub fn draw_tetromino(shape: &TetrominoShape, grid_x: i32, grid_y: i32, color: Color, scale: i32) {
+    for row in 0..shape.size() {
+        for col in 0..shape.size() {
             if shape[row][col] != 0 {
-                draw_rectangle(
-                    (grid_x + col as i32) as f32 * BLOCK_SIZE,
-                    (grid_y + row as i32) as f32 * BLOCK_SIZE,
-                    BLOCK_SIZE - 2.0,
-                    BLOCK_SIZE - 2.0,
-                    color,
-                );
+                for (x, y) in block_cells(grid_x, grid_y, row, col, scale) {
+                    draw_rectangle(
+                        x as f32 * block_size(),
+                        y as f32 * block_size(),
+                        block_size() - 2.0,
+                        block_size() - 2.0,
+                        color,
+                    );
+                }
             }
         }
     }
 }
 
 /// Bag of 7 system for tetromino generation
+#[derive(Clone)]
 pub struct TetrominoBag {
+    contents: Vec<usize>,
     bag: Vec<usize>,
     index: usize,
+    /// Seed of a bag with its own random sequence, independent of the global generator.
+    seed: Option<u64>,
+    /// Number of bags shuffled so far, mixed into the seed of each new bag.
+    shuffles: u64,
 }
 
 /// Implementation of TetrominoBag
 impl TetrominoBag {
-    pub fn new() -> Self {
-        let mut bag: Vec<usize> = (0..7).collect();
-        bag.shuffle();
-        Self { bag, index: 0 }
+    /// Creates a bag where piece `i` appears `weights[i]` times per cycle.
+    /// With a weight of 1 for each of the 7 tetrominoes this is the classic bag of 7.
+    ///
+    /// # Arguments
+    /// * `weights` - The randomizer weight of each piece; at least one must be nonzero
+    pub fn new(weights: &[u32]) -> Self {
+        let contents: Vec<usize> = weights
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, &weight)| std::iter::repeat_n(idx, weight as usize))
+            .collect();
+        let mut bag = Self { contents, bag: Vec::new(), index: 0, seed: None, shuffles: 0 };
+        bag.refill();
+        bag
+    }
+
+    /// Creates a bag that deals the same pieces for the same seed, without touching the global
+    /// random generator used by the rest of the game.
+    ///
+    /// # Arguments
+    /// * `weights` - The randomizer weight of each piece; at least one must be nonzero
+    /// * `seed` - The seed of the piece sequence
+    pub fn seeded(weights: &[u32], seed: u64) -> Self {
+        let mut bag = Self::new(weights);
+        // Replace the first bag, shuffled from the global generator, with the seeded one
+        bag.seed = Some(seed);
+        bag.shuffles = 0;
+        bag.refill();
+        bag
+    }
+
+    /// Starts a new shuffled bag, from the bag's own seed if it has one.
+    fn refill(&mut self) {
+        self.bag = self.contents.clone();
+        match self.seed {
+            Some(seed) => {
+                let generator = RandGenerator::new();
+                generator.srand(seed.wrapping_add(self.shuffles));
+                self.bag.shuffle_with_state(&generator);
+            }
+            None => self.bag.shuffle(),
+        }
+        self.shuffles += 1;
+        self.index = 0;
     }
 
     /// Get the next tetromino index from the bag
@@ -104,9 +238,7 @@ impl TetrominoBag {
         let idx = self.bag[self.index];
         self.index += 1;
         if self.index >= self.bag.len() {
-            self.bag = (0..7).collect();
-            self.bag.shuffle();
-            self.index = 0;
+            self.refill();
         }
         idx
     }
@@ -115,4 +247,104 @@ impl TetrominoBag {
     pub fn peek(&self) -> usize {
         self.bag[self.index]
     }
+
+    /// Replace the next tetromino index (used to pick pieces in practice)
+    pub fn replace_next(&mut self, idx: usize) {
+        self.bag[self.index] = idx;
+    }
+}
+
+/// Randomizer of the NES game: each piece is rolled uniformly among `count` pieces plus one extra
+/// value; a roll of the extra value or of the previous piece is rerolled once among the pieces.
+#[derive(Clone)]
+pub struct NesRandomizer {
+    count: usize,
+    upcoming: usize,
+}
+
+/// Implementation of NesRandomizer
+impl NesRandomizer {
+    /// Creates a randomizer over `count` pieces, with the first piece already rolled.
+    pub fn new(count: usize) -> Self {
+        let upcoming = gen_range(0, count);
+        Self { count, upcoming }
+    }
+
+    /// Get the next piece index and roll the one after it
+    pub fn next(&mut self) -> usize {
+        let idx = self.upcoming;
+        let roll = gen_range(0, self.count + 1);
+        self.upcoming = if roll == self.count || roll == idx {
+            gen_range(0, self.count)
+        } else {
+            roll
+        };
+        idx
+    }
+
+    /// Peek at the next piece index without rolling
+    pub fn peek(&self) -> usize {
+        self.upcoming
+    }
+}
+
+/// Source of upcoming pieces: a random bag, the NES randomizer, a fixed sequence that runs out,
+/// or a scripted sequence followed by a bag.
+#[derive(Clone)]
+pub enum PieceQueue {
+    Bag(TetrominoBag),
+    Nes(NesRandomizer),
+    Sequence { pieces: Vec<usize>, index: usize },
+    Scripted { pieces: Vec<usize>, index: usize, bag: TetrominoBag },
+}
+
+/// Implementation of PieceQueue
+impl PieceQueue {
+    /// Get the next piece index, or None if a fixed sequence has run out
+    pub fn next(&mut self) -> Option<usize> {
+        match self {
+            PieceQueue::Bag(bag) => Some(bag.next()),
+            PieceQueue::Nes(randomizer) => Some(randomizer.next()),
+            PieceQueue::Sequence { pieces, index } => {
+                let idx = pieces.get(*index).copied();
+                *index += 1;
+                idx
+            }
+            PieceQueue::Scripted { pieces, index, bag } => match pieces.get(*index) {
+                Some(&idx) => {
+                    *index += 1;
+                    Some(idx)
+                }
+                None => Some(bag.next()),
+            },
+        }
+    }
+
+    /// Peek at the next piece index without advancing the queue
+    pub fn peek(&self) -> Option<usize> {
+        match self {
+            PieceQueue::Bag(bag) => Some(bag.peek()),
+            PieceQueue::Nes(randomizer) => Some(randomizer.peek()),
+            PieceQueue::Sequence { pieces, index } => pieces.get(*index).copied(),
+            PieceQueue::Scripted { pieces, index, bag } => {
+                Some(pieces.get(*index).copied().unwrap_or_else(|| bag.peek()))
+            }
+        }
+    }
+
+    /// Replace the next piece index, so the given piece is dealt next
+    pub fn set_next(&mut self, idx: usize) {
+        match self {
+            PieceQueue::Bag(bag) => bag.replace_next(idx),
+            PieceQueue::Nes(randomizer) => randomizer.upcoming = idx,
+            PieceQueue::Sequence { pieces, index } => match pieces.get_mut(*index) {
+                Some(next) => *next = idx,
+                None => pieces.push(idx),
+            },
+            PieceQueue::Scripted { pieces, index, bag } => match pieces.get_mut(*index) {
+                Some(next) => *next = idx,
+                None => bag.replace_next(idx),
+            },
+        }
+    }
 }
@@ -3,7 +3,12 @@
 // Principles and design choices:
 //
 // - Defines helper functions for drawing centered and bottom-aligned text, using a flexible alignment enum.
-// - Implements the difficulty selection menu, including keyboard navigation and visual highlighting of the selected option.
+// - Implements the start menu (mode, difficulty and other options), with visual highlighting of the selected row.
+// - Provides a scrolling list screen (e.g. for choosing a puzzle or editing the settings).
+// - Provides the results overlay shown when a game ends, and a shared time formatter.
+// - Provides the name entry prompts (a new high score, a new profile) and a table screen for the high
+//   score tables.
+// - Provides a banner across the top of the window for short prompts (e.g. confirming to quit).
 // - Uses consistent layout and color choices for clarity and a modern look.
 // - Designed for easy integration with the main game loop and extensibility for future UI elements.
 
@@ -54,31 +59,206 @@ pub fn draw_bottom_centered_text(text: &str, font_size: f32, color: Color) {
     draw_aligned_text(text, font_size, color, VerticalAlign::Bottom(20.0));
 }
 
-/// Draws the difficulty selection menu with the given selected option highlighted.
+/// Draws the start menu: a title and a list of option rows, with the selected row highlighted.
+/// Each row is shown as "Label: < Value >" to hint that Left/Right changes its value.
 ///
 /// # Arguments
-/// * `selected` - The index of the currently selected difficulty option
-pub fn draw_difficulty_menu(selected: usize) {    
-    // Draw title
-    let title = "Select difficulty";
+/// * `title` - The menu title
+/// * `subtitle` - The line under the title (the player's profile)
+/// * `rows` - The (label, value) pairs of the option rows
+/// * `selected` - The index of the currently selected row
+pub fn draw_start_menu(title: &str, subtitle: &str, rows: &[(&str, String)], selected: usize) {
+    // Draw title and subtitle
     let font_size = 48.0;
     let text_dims = measure_text(title, None, font_size as u16, 1.0);
     let x = (screen_width() - text_dims.width) / 2.0;
-    let y = screen_height() / 2.0 - 120.0;
-    draw_text(title, x, y, font_size, ORANGE);
+    draw_text(title, x, 100.0, font_size, ORANGE);
+    let text_dims = measure_text(subtitle, None, 22, 1.0);
+    draw_text(subtitle, (screen_width() - text_dims.width) / 2.0, 135.0, 22.0, GRAY);
 
-    // Draw options
-    let options = ["Easy", "Medium", "Hard"];
-    for (i, &option) in options.iter().enumerate() {
+    // Draw option rows
+    for (i, (label, value)) in rows.iter().enumerate() {
         let color = if i == selected { BLUE } else { WHITE };
-        let symbol = if i == selected { "> " } else { "  " }; // Star for selected, space for others
-        let text = format!("{}{}", symbol, option);
-        draw_text(
-            &text,
-            screen_width() / 2.0 - 80.0,
-            screen_height() / 2.0 + (i as f32 - 1.0) * 50.0,
-            40.0,
-            color,
-        );
+        let symbol = if i == selected { "> " } else { "  " };
+        let text = format!("{}{}: < {} >", symbol, label, value);
+        draw_text(&text, 30.0, 180.0 + i as f32 * 44.0, 32.0, color);
+    }
+
+    // Draw navigation hint
+    draw_aligned_text(
+        "Arrows  E: editor  H: scores  S: settings  K: keys",
+        20.0,
+        GRAY,
+        VerticalAlign::Bottom(80.0),
+    );
+}
+
+/// Draws a note about the selected option above the start menu's navigation hint.
+///
+/// # Arguments
+/// * `text` - The note to draw
+/// * `color` - The color of the text
+pub fn draw_menu_note(text: &str, color: Color) {
+    draw_aligned_text(text, 20.0, color, VerticalAlign::Bottom(110.0));
+}
+
+/// Draws a banner across the top of the window, over whatever screen is shown.
+///
+/// # Arguments
+/// * `text` - The text of the banner
+pub fn draw_banner(text: &str) {
+    draw_rectangle(0.0, 0.0, screen_width(), 40.0, Color::new(0.0, 0.0, 0.0, 0.85));
+    draw_aligned_text(text, 24.0, YELLOW, VerticalAlign::Bottom(screen_height() - 27.0));
+}
+
+/// Draws a scrolling list screen: a title and a list of items, with the selected item highlighted.
+/// The list scrolls to keep the selected item visible.
+///
+/// # Arguments
+/// * `title` - The screen title
+/// * `items` - The text and color of each item
+/// * `selected` - The index of the currently selected item
+/// * `hint` - The navigation hint at the bottom
+pub fn draw_list(title: &str, items: &[(String, Color)], selected: usize, hint: &str) {
+    // Draw title
+    let font_size = 48.0;
+    let text_dims = measure_text(title, None, font_size as u16, 1.0);
+    let x = (screen_width() - text_dims.width) / 2.0;
+    draw_text(title, x, 100.0, font_size, ORANGE);
+
+    // Draw the visible window of items
+    let line_height = 32.0;
+    let visible = (((screen_height() - 120.0 - 150.0) / line_height) as usize).max(1);
+    let first = selected.saturating_sub(visible - 1);
+    for (i, (text, color)) in items.iter().enumerate().skip(first).take(visible) {
+        let (symbol, color) = if i == selected { ("> ", BLUE) } else { ("  ", *color) };
+        let y = 150.0 + (i - first) as f32 * line_height;
+        draw_text(&format!("{}{}", symbol, text), 30.0, y, 24.0, color);
+    }
+
+    // Draw navigation hint
+    draw_aligned_text(hint, 20.0, GRAY, VerticalAlign::Bottom(80.0));
+}
+
+/// Contents of the screen shown when a game ends: a title and the lines of results below it.
+pub struct Results {
+    pub title: String,
+    pub title_color: Color,
+    pub lines: Vec<(String, Color)>,
+}
+
+/// Implementation of Results methods.
+impl Results {
+    /// Creates results with the given title and no lines.
+    pub fn new(title: &str, title_color: Color) -> Self {
+        Self {
+            title: title.to_owned(),
+            title_color,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Adds a line of results.
+    pub fn push(&mut self, text: String, color: Color) {
+        self.lines.push((text, color));
+    }
+}
+
+/// Draws a results screen: the title in the center and the result lines below it.
+/// Long results that would reach the bottom prompt are split into two columns.
+///
+/// # Arguments
+/// * `results` - The results to draw
+pub fn draw_results(results: &Results) {
+    draw_centered_text(&results.title, 60.0, results.title_color);
+
+    let lines = &results.lines;
+    let font_size = 20.0;
+    let line_height = font_size + 2.0;
+    let top = screen_height() / 2.0 + 50.0;
+    let max_rows = (((screen_height() - 70.0 - top) / line_height) as usize).max(1);
+
+    let (columns, rows) = if lines.len() > max_rows {
+        (2, lines.len().div_ceil(2))
+    } else {
+        (1, lines.len())
+    };
+    let column_width = screen_width() / columns as f32;
+    for (i, (text, color)) in lines.iter().enumerate() {
+        let column = (i / rows.max(1)) as f32;
+        let row = (i % rows.max(1)) as f32;
+        let text_dims = measure_text(text, None, font_size as u16, 1.0);
+        let x = column * column_width + (column_width - text_dims.width) / 2.0;
+        draw_text(text, x, top + row * line_height, font_size, *color);
+    }
+}
+
+/// Draws the prompt for the name of a new high score, over the dimmed board.
+///
+/// # Arguments
+/// * `rank` - The 0-based position of the new entry in its table
+/// * `name` - The name typed so far
+pub fn draw_name_entry(rank: usize, name: &str) {
+    draw_centered_text(&format!("New high score! #{}", rank + 1), 40.0, GOLD);
+    let text = format!("Name: {}_", name);
+    let text_dims = measure_text(&text, None, 32, 1.0);
+    draw_text(&text, (screen_width() - text_dims.width) / 2.0, screen_height() / 2.0 + 60.0, 32.0, WHITE);
+    draw_bottom_centered_text("Type your name, Enter: save", 28.0, YELLOW);
+}
+
+/// Draws the prompt for the name of a new profile.
+///
+/// # Arguments
+/// * `name` - The name typed so far
+pub fn draw_profile_entry(name: &str) {
+    draw_centered_text("New profile", 40.0, GOLD);
+    let text = format!("Name: {}_", name);
+    let text_dims = measure_text(&text, None, 32, 1.0);
+    draw_text(&text, (screen_width() - text_dims.width) / 2.0, screen_height() / 2.0 + 60.0, 32.0, WHITE);
+    draw_bottom_centered_text("Enter: create   Esc: cancel", 28.0, YELLOW);
+}
+
+/// Draws a table screen: a title, a subtitle and rows of cells in fixed-width columns.
+///
+/// # Arguments
+/// * `title` - The screen title
+/// * `subtitle` - The line under the title (e.g. the table shown)
+/// * `columns` - The header and width in pixels of each column
+/// * `rows` - The cells of each row, one per column
+/// * `hint` - The navigation hint at the bottom
+pub fn draw_table(title: &str, subtitle: &str, columns: &[(&str, f32)], rows: &[Vec<String>], hint: &str) {
+    // Draw title and subtitle
+    let font_size = 48.0;
+    let text_dims = measure_text(title, None, font_size as u16, 1.0);
+    draw_text(title, (screen_width() - text_dims.width) / 2.0, 80.0, font_size, ORANGE);
+    let text_dims = measure_text(subtitle, None, 24, 1.0);
+    draw_text(subtitle, (screen_width() - text_dims.width) / 2.0, 120.0, 24.0, SKYBLUE);
+
+    // Draw the header and the rows, column by column
+    let line_height = 28.0;
+    let mut x = 10.0;
+    for (i, (header, width)) in columns.iter().enumerate() {
+        draw_text(header, x, 160.0, 20.0, GRAY);
+        for (j, row) in rows.iter().enumerate() {
+            if let Some(cell) = row.get(i) {
+                draw_text(cell, x, 190.0 + j as f32 * line_height, 20.0, WHITE);
+            }
+        }
+        x += width;
     }
-}
\ No newline at end of file
+    if rows.is_empty() {
+        draw_aligned_text("No scores yet", 24.0, LIGHTGRAY, VerticalAlign::Center);
+    }
+
+    // Draw navigation hint
+    draw_aligned_text(hint, 20.0, GRAY, VerticalAlign::Bottom(80.0));
+}
+
+/// Formats a time in seconds as "m:ss.mmm".
+///
+/// # Arguments
+/// * `seconds` - The time to format
+pub fn format_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}